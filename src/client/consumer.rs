@@ -1,6 +1,13 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
 use chrono::{DateTime, TimeZone, Utc};
 use log::{debug, error, info};
 use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::error::{KafkaError, KafkaResult};
+use rdkafka::message::{BorrowedMessage, Headers};
+use rdkafka::{Offset, TopicPartitionList};
 
 use rdkafka::message::FromBytes;
 use rdkafka::{Message, Timestamp};
@@ -10,6 +17,7 @@ use strum_macros;
 use strum_macros::{Display, EnumIter, EnumString, IntoStaticStr};
 use tokio::sync::oneshot::Receiver;
 
+use crate::client::context::KafkyClientContext;
 use crate::{KafkyClient, KafkyError};
 
 pub fn serialize_dt<S>(dt: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
@@ -22,6 +30,39 @@ where
     }
 }
 
+/// message header, consumed values are decoded as UTF-8 replacing the invalid sequences
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KafkyHeader {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl KafkyHeader {
+    /// `key=value`, or `key` alone for a null value
+    pub fn parse(header: &str) -> Result<Self, KafkyError> {
+        let (key, value) = match header.split_once('=') {
+            Some((key, value)) => (key, Some(value.to_string())),
+            None => (header, None),
+        };
+        if key.trim().is_empty() {
+            return Err(KafkyError::InvalidHeader(header.to_string()));
+        }
+        Ok(KafkyHeader {
+            key: key.to_string(),
+            value,
+        })
+    }
+}
+
+impl std::fmt::Display for KafkyHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}={}", self.key, value),
+            None => write!(f, "{}", self.key),
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct KafkyConsumerMessage<'a, K: ?Sized + FromBytes, P: ?Sized + FromBytes> {
     key: Option<&'a K>,
@@ -34,6 +75,8 @@ pub struct KafkyConsumerMessage<'a, K: ?Sized + FromBytes, P: ?Sized + FromBytes
         skip_serializing_if = "Option::is_none"
     )]
     timestamp: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<KafkyHeader>,
 }
 
 impl<'a, K: ?Sized + FromBytes, P: ?Sized + FromBytes> KafkyConsumerMessage<'a, K, P> {
@@ -49,6 +92,15 @@ impl<'a, K: ?Sized + FromBytes, P: ?Sized + FromBytes> KafkyConsumerMessage<'a,
     pub fn topic(&self) -> &'a str {
         self.topic
     }
+    pub fn headers(&self) -> &Vec<KafkyHeader> {
+        &self.headers
+    }
+    pub fn partition(&self) -> i32 {
+        self.partition
+    }
+    pub fn offset(&self) -> i64 {
+        self.offset
+    }
 }
 
 #[derive(EnumString, Display, EnumIter, PartialEq, IntoStaticStr, Debug)]
@@ -67,6 +119,187 @@ impl<'a> KafkyConsumerOffset {
         }
         offset_values
     }
+
+    fn as_rdkafka_offset(&self) -> Offset {
+        match self {
+            KafkyConsumerOffset::Earliest => Offset::Beginning,
+            KafkyConsumerOffset::Latest => Offset::End,
+        }
+    }
+}
+
+/// where the consumer starts, the partitions are assigned instead of subscribing the topics
+#[derive(Debug, PartialEq)]
+pub enum KafkyConsumerPosition {
+    /// first message with a timestamp greater or equal to the given milliseconds
+    Timestamp(i64),
+    /// the offset of the listed partitions, `default` for the others
+    Offsets {
+        default: Option<i64>,
+        partitions: BTreeMap<i32, i64>,
+    },
+    /// the last messages of each partition
+    Last(i64),
+}
+
+type KafkyTopicPartition = (String, i32);
+
+/// with `until`, time to wait for a message once the time has come
+const UNTIL_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// the stop conditions of a consumption, updated with every received message
+#[derive(Default)]
+struct KafkyConsumeProgress {
+    /// high watermarks of the start not reached yet
+    partition_ends: Option<HashMap<KafkyTopicPartition, i64>>,
+    /// assigned partitions read to the end, when `exit_on_eof`
+    eof_partitions: Option<HashSet<KafkyTopicPartition>>,
+    /// assigned partitions with a message after `until`
+    until_partitions: HashSet<KafkyTopicPartition>,
+    until: Option<i64>,
+}
+
+impl KafkyConsumeProgress {
+    fn new(
+        consumer: &StreamConsumer<KafkyClientContext>,
+        properties: &KafkyConsumeProperties,
+    ) -> Result<Self, KafkyError> {
+        Ok(KafkyConsumeProgress {
+            partition_ends: if properties.stop_at_end {
+                Some(Self::partition_ends(consumer)?)
+            } else {
+                None
+            },
+            eof_partitions: properties.exit_on_eof.then(HashSet::new),
+            until: properties.until,
+            ..Default::default()
+        })
+    }
+
+    fn partition_ends(
+        consumer: &StreamConsumer<KafkyClientContext>,
+    ) -> Result<HashMap<KafkyTopicPartition, i64>, KafkyError> {
+        let timeout = Duration::from_secs(30);
+        let mut ends = HashMap::new();
+        for element in consumer.assignment()?.elements() {
+            let (low, high) =
+                consumer.fetch_watermarks(element.topic(), element.partition(), timeout)?;
+            let start = match element.offset() {
+                Offset::Offset(offset) => offset.max(low),
+                Offset::End => high,
+                _ => low,
+            };
+            if start < high {
+                ends.insert((element.topic().to_string(), element.partition()), high);
+            }
+        }
+        debug!("partition ends {:?}", &ends);
+        Ok(ends)
+    }
+
+    /// registers a received message, returns whether it has to be handed to the message consumer
+    fn message(
+        &mut self,
+        topic: &str,
+        partition: i32,
+        offset: i64,
+        timestamp: Option<i64>,
+    ) -> bool {
+        let key = (topic.to_string(), partition);
+        self.position(&key, offset + 1);
+        if let Some(eof_partitions) = &mut self.eof_partitions {
+            eof_partitions.remove(&key);
+        }
+        if let (Some(until), Some(timestamp)) = (self.until, timestamp) {
+            if timestamp > until {
+                self.until_partitions.insert(key);
+                return false;
+            }
+        }
+        true
+    }
+
+    /// a partition read up to its end of the start is done
+    fn position(&mut self, key: &KafkyTopicPartition, position: i64) {
+        if let Some(partition_ends) = &mut self.partition_ends {
+            if partition_ends.get(key).is_some_and(|end| position >= *end) {
+                partition_ends.remove(key);
+            }
+        }
+    }
+
+    /// the partition EOF event has the partition number only, the topics sharing it are checked
+    fn partition_eof(
+        &mut self,
+        consumer: &StreamConsumer<KafkyClientContext>,
+        partition: i32,
+    ) -> Result<(), KafkyError> {
+        if self.partition_ends.is_some() {
+            // the last offset of a transactional partition is a commit marker, never delivered
+            // but passed by the position
+            for element in consumer.position()?.elements() {
+                if let Offset::Offset(position) = element.offset() {
+                    self.position(
+                        &(element.topic().to_string(), element.partition()),
+                        position,
+                    );
+                }
+            }
+        }
+        let eof_partitions = match &mut self.eof_partitions {
+            Some(eof_partitions) => eof_partitions,
+            None => return Ok(()),
+        };
+        let positions = consumer.position()?;
+        let candidates: Vec<_> = positions
+            .elements()
+            .into_iter()
+            .filter(|e| e.partition() == partition)
+            .collect();
+        for element in &candidates {
+            let at_end = candidates.len() == 1
+                || match element.offset() {
+                    Offset::Offset(position) => {
+                        let (_, high) = consumer.fetch_watermarks(
+                            element.topic(),
+                            partition,
+                            Duration::from_secs(30),
+                        )?;
+                        position >= high
+                    }
+                    _ => false,
+                };
+            if at_end {
+                eof_partitions.insert((element.topic().to_string(), partition));
+            }
+        }
+        Ok(())
+    }
+
+    fn is_done(&self, consumer: &StreamConsumer<KafkyClientContext>) -> bool {
+        if self.partition_ends.as_ref().is_some_and(|e| e.is_empty()) {
+            return true;
+        }
+        if self.eof_partitions.is_none() && self.until_partitions.is_empty() {
+            return false;
+        }
+        // every assigned partition is either drained or past `until`
+        match consumer.assignment() {
+            Ok(assignment) => {
+                let elements = assignment.elements();
+                !elements.is_empty()
+                    && elements.iter().all(|e| {
+                        let key = (e.topic().to_string(), e.partition());
+                        self.until_partitions.contains(&key)
+                            || self
+                                .eof_partitions
+                                .as_ref()
+                                .is_some_and(|eof| eof.contains(&key))
+                    })
+            }
+            Err(_) => false,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -75,6 +308,39 @@ pub struct KafkyConsumeProperties<'a> {
     pub consumer_group: &'a str,
     pub offset: KafkyConsumerOffset,
     pub auto_commit: bool,
+    pub position: Option<KafkyConsumerPosition>,
+    /// partitions assigned to the consumer, all when `None`
+    pub partitions: Option<Vec<i32>>,
+    /// stops once the assigned partitions are read up to the high watermarks of the start
+    pub stop_at_end: bool,
+    /// stops once every assigned partition is read to the end
+    pub exit_on_eof: bool,
+    /// stops once every partition has a message after the time in milliseconds,
+    /// or when no message arrives after the time
+    pub until: Option<i64>,
+}
+
+impl<'a> KafkyConsumeProperties<'a> {
+    /// the partitions are assigned without joining the consumer group
+    fn is_manual_assignment(&self) -> bool {
+        self.position.is_some() || self.partitions.is_some()
+    }
+
+    /// the partitions to assign, all when empty: the offsets given per partition add them
+    /// to the selection, but a default offset without selection is for every partition
+    fn selected_partitions(&self) -> Vec<i32> {
+        let mut selected_partitions = self.partitions.clone().unwrap_or_default();
+        if let Some(KafkyConsumerPosition::Offsets {
+            default,
+            partitions,
+        }) = &self.position
+        {
+            if default.is_none() || !selected_partitions.is_empty() {
+                selected_partitions.extend(partitions.keys());
+            }
+        }
+        selected_partitions
+    }
 }
 
 impl<'a> KafkyClient<'a> {
@@ -88,52 +354,143 @@ impl<'a> KafkyClient<'a> {
         stop_rx: Option<Receiver<bool>>,
         mut message_consumer: F,
     ) -> Result<(), KafkyError> {
-        let mut consumer_builder = self.config_builder();
+        let mut consumer_builder = self.config_builder()?;
+        // assigned partitions don't join the group, it's needed only to commit the offsets
+        if !properties.is_manual_assignment() || properties.auto_commit {
+            consumer_builder.set("group.id", properties.consumer_group);
+        }
         consumer_builder
-            .set("group.id", properties.consumer_group)
             .set("enable.auto.commit", properties.auto_commit.to_string())
             .set("session.timeout.ms", "6000")
-            .set("auto.offset.reset", properties.offset.to_string());
+            .set("auto.offset.reset", properties.offset.to_string())
+            // the EOF events tell when a partition ending with a transaction marker is read
+            .set(
+                "enable.partition.eof",
+                (properties.exit_on_eof || properties.stop_at_end).to_string(),
+            );
+        self.apply_properties(&mut consumer_builder)?;
 
         debug!("Consumer properties: {:?}", &consumer_builder);
-        let consumer: StreamConsumer = consumer_builder.create()?;
-        consumer
-            .subscribe(properties.topics)
-            .expect("subscribe error");
+        let consumer: Arc<StreamConsumer<KafkyClientContext>> =
+            Arc::new(consumer_builder.create_with_context(KafkyClientContext)?);
+        self.keep_oauth_token(&consumer)?;
+        if properties.is_manual_assignment() {
+            let assignment = Self::manual_assignment(&consumer, properties)?;
+            debug!("assignment {:?}", &assignment);
+            consumer.assign(&assignment)?;
+        } else {
+            consumer
+                .subscribe(properties.topics)
+                .expect("subscribe error");
+        }
         info!("subscription properties {:?}", properties);
-
-        if let Some(stop_rx) = stop_rx {
-            tokio::select! {
-                _ = async {
-                loop{
-                        if !Self::process_message(&consumer, &mut message_consumer).await {
-                            break;
+        let mut progress = KafkyConsumeProgress::new(&consumer, properties)?;
+        let consume = async {
+            while !progress.is_done(&consumer) {
+                let message = match progress.until {
+                    // no message after `until` and the idle time means nothing else is coming
+                    Some(until) => {
+                        let wait = Duration::from_millis(
+                            (until - Utc::now().timestamp_millis()).max(0) as u64,
+                        ) + UNTIL_IDLE_TIMEOUT;
+                        match tokio::time::timeout(wait, consumer.recv()).await {
+                            Ok(message) => message,
+                            Err(_) => break,
                         }
-                } }=>{},
-                _ = stop_rx =>{
+                    }
+                    None => consumer.recv().await,
+                };
+                if !Self::process_message(&consumer, message, &mut message_consumer, &mut progress)?
+                {
+                    break;
+                }
+            }
+            Ok(())
+        };
+
+        match stop_rx {
+            Some(stop_rx) => tokio::select! {
+                result = consume => result,
+                _ = stop_rx => {
                     debug!("Received close signal, stopping consumer");
+                    Ok(())
                 }
+            },
+            None => consume.await,
+        }
+    }
+
+    /// the selected partitions of the topics, all when none is selected, at the offset of the position
+    fn manual_assignment(
+        consumer: &StreamConsumer<KafkyClientContext>,
+        properties: &KafkyConsumeProperties,
+    ) -> Result<TopicPartitionList, KafkyError> {
+        let timeout = Duration::from_secs(30);
+        let selected_partitions = properties.selected_partitions();
+        let mut assignment = TopicPartitionList::new();
+        for topic in properties.topics {
+            let metadata = consumer.fetch_metadata(Some(topic), timeout)?;
+            let partitions: Vec<i32> = metadata
+                .topics()
+                .iter()
+                .find(|t| t.name() == *topic && t.error().is_none())
+                .map(|t| t.partitions().iter().map(|p| p.id()).collect())
+                .filter(|partitions: &Vec<i32>| !partitions.is_empty())
+                .ok_or_else(|| KafkyError::TopicNotFound(topic.to_string()))?;
+            if let Some(missing) = selected_partitions.iter().find(|p| !partitions.contains(p)) {
+                return Err(KafkyError::PartitionNotFound(topic.to_string(), *missing));
             }
-        } else {
-            loop {
-                Self::process_message(&consumer, &mut message_consumer).await;
+            for partition in partitions {
+                if !selected_partitions.is_empty() && !selected_partitions.contains(&partition) {
+                    continue;
+                }
+                let offset = match &properties.position {
+                    Some(KafkyConsumerPosition::Timestamp(timestamp)) => Offset::Offset(*timestamp),
+                    Some(KafkyConsumerPosition::Last(count)) => {
+                        let (low, high) = consumer.fetch_watermarks(topic, partition, timeout)?;
+                        Offset::Offset((high - count).max(low))
+                    }
+                    Some(KafkyConsumerPosition::Offsets {
+                        default,
+                        partitions,
+                    }) => partitions
+                        .get(&partition)
+                        .or(default.as_ref())
+                        .map(|offset| Offset::Offset(*offset))
+                        .unwrap_or_else(|| properties.offset.as_rdkafka_offset()),
+                    None => properties.offset.as_rdkafka_offset(),
+                };
+                assignment.add_partition_offset(topic, partition, offset)?;
             }
         }
-
-        Ok(())
+        match &properties.position {
+            Some(KafkyConsumerPosition::Timestamp(_)) => {
+                Ok(consumer.offsets_for_times(assignment, timeout)?)
+            }
+            _ => Ok(assignment),
+        }
     }
 
-    async fn process_message<
+    fn process_message<
         K: ?Sized + FromBytes,
         P: ?Sized + FromBytes,
         F: FnMut(Result<KafkyConsumerMessage<K, P>, KafkyError>) -> bool,
     >(
-        consumer: &StreamConsumer,
+        consumer: &StreamConsumer<KafkyClientContext>,
+        kafky_msg: KafkaResult<BorrowedMessage<'_>>,
         mut message_consumer: F,
-    ) -> bool {
-        let kafky_msg = consumer.recv().await;
+        progress: &mut KafkyConsumeProgress,
+    ) -> Result<bool, KafkyError> {
         match kafky_msg {
             Ok(m) => {
+                if !progress.message(
+                    m.topic(),
+                    m.partition(),
+                    m.offset(),
+                    m.timestamp().to_millis(),
+                ) {
+                    return Ok(true);
+                }
                 let opt_payload: Option<&P> = match m.payload_view::<P>() {
                     None => None,
                     Some(Ok(s)) => Some(s),
@@ -155,29 +512,107 @@ impl<'a> KafkyClient<'a> {
                     let creation_time = match m.timestamp() {
                         Timestamp::NotAvailable => None,
                         Timestamp::CreateTime(creation_time) => {
-                            Some(Utc.timestamp_millis(creation_time))
+                            Utc.timestamp_millis_opt(creation_time).single()
                         }
                         Timestamp::LogAppendTime(log_appended_msec) => {
-                            Some(Utc.timestamp_millis(log_appended_msec))
+                            Utc.timestamp_millis_opt(log_appended_msec).single()
                         }
                     };
 
-                    message_consumer(Ok(KafkyConsumerMessage {
+                    Ok(message_consumer(Ok(KafkyConsumerMessage {
                         key,
                         payload,
                         topic: m.topic(),
                         partition: m.partition(),
                         offset: m.offset(),
                         timestamp: creation_time,
-                    }))
+                        headers: m
+                            .headers()
+                            .map(|headers| {
+                                headers
+                                    .iter()
+                                    .map(|header| KafkyHeader {
+                                        key: header.key.to_string(),
+                                        value: header
+                                            .value
+                                            .map(|v| String::from_utf8_lossy(v).to_string()),
+                                    })
+                                    .collect()
+                            })
+                            .unwrap_or_default(),
+                    })))
                 } else {
-                    true
+                    Ok(true)
                 }
             }
+            Err(KafkaError::PartitionEOF(partition)) => {
+                progress.partition_eof(consumer, partition)?;
+                Ok(true)
+            }
             Err(err) => {
                 error!("Error consuming messages:{}", err);
-                false
+                Err(err.into())
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_test() {
+        let mut progress = KafkyConsumeProgress {
+            partition_ends: Some(HashMap::from([(("topic".to_string(), 0), 10)])),
+            until: Some(1_000),
+            ..Default::default()
+        };
+        assert!(progress.message("topic", 0, 8, Some(900)));
+        assert!(!progress.message("topic", 1, 3, Some(1_001)));
+        assert!(progress
+            .until_partitions
+            .contains(&("topic".to_string(), 1)));
+        assert!(progress.message("topic", 0, 9, None));
+        assert!(progress.partition_ends.unwrap().is_empty());
+
+        // the offset 9 is the commit marker of a transaction
+        let mut transactional = KafkyConsumeProgress {
+            partition_ends: Some(HashMap::from([(("topic".to_string(), 0), 10)])),
+            ..Default::default()
+        };
+        assert!(transactional.message("topic", 0, 8, None));
+        assert_eq!(transactional.partition_ends.as_ref().unwrap().len(), 1);
+        transactional.position(&("topic".to_string(), 0), 10);
+        assert!(transactional.partition_ends.unwrap().is_empty());
+    }
+
+    #[test]
+    fn selected_partitions_test() {
+        let topics = vec!["topic"];
+        let properties =
+            |partitions: Option<Vec<i32>>, default: Option<i64>| KafkyConsumeProperties {
+                topics: &topics,
+                consumer_group: "kafky",
+                offset: KafkyConsumerOffset::Earliest,
+                auto_commit: false,
+                position: Some(KafkyConsumerPosition::Offsets {
+                    default,
+                    partitions: BTreeMap::from([(2, 42)]),
+                }),
+                partitions,
+                stop_at_end: false,
+                exit_on_eof: false,
+                until: None,
+            };
+        // --offset 2:42
+        assert_eq!(properties(None, None).selected_partitions(), vec![2]);
+        // --offset 10 --offset 2:42 reads every partition
+        assert!(properties(None, Some(10)).selected_partitions().is_empty());
+        // -p 0 --offset 10 --offset 2:42
+        assert_eq!(
+            properties(Some(vec![0]), Some(10)).selected_partitions(),
+            vec![0, 2]
+        );
+    }
+}
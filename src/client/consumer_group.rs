@@ -121,6 +121,11 @@ impl<'a> KafkyClient<'a> {
                 consumer_group: "kafky",
                 offset: KafkyConsumerOffset::Earliest,
                 auto_commit: false,
+                position: None,
+                partitions: None,
+                stop_at_end: false,
+                exit_on_eof: false,
+                until: None,
             },
             Some(timeout_rx),
             |consumer_offset_msg_res| {
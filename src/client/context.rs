@@ -0,0 +1,23 @@
+use rdkafka::consumer::ConsumerContext;
+use rdkafka::producer::{DeliveryResult, ProducerContext};
+use rdkafka::ClientContext;
+
+/// rdkafka client context shared by every kafky client; the OAUTHBEARER tokens aren't
+/// refreshed through it but set on the clients by `KafkyOAuthTokenProvider::keep_token`,
+/// which doesn't depend on the client polling its main queue
+pub struct KafkyClientContext;
+
+impl ClientContext for KafkyClientContext {}
+
+impl ConsumerContext for KafkyClientContext {}
+
+impl ProducerContext for KafkyClientContext {
+    type DeliveryOpaque = ();
+
+    fn delivery(
+        &self,
+        _delivery_result: &DeliveryResult<'_>,
+        _delivery_opaque: Self::DeliveryOpaque,
+    ) {
+    }
+}
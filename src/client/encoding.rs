@@ -0,0 +1,141 @@
+use std::fmt::{Display, Formatter, Write};
+
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use strum_macros::{Display, EnumString, EnumVariantNames};
+
+/// rendering of the consumed keys and values
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, Display, EnumVariantNames)]
+#[strum(serialize_all = "lowercase")]
+pub enum KafkyEncoding {
+    /// invalid sequences are replaced
+    Utf8,
+    Hex,
+    Base64,
+    /// offset, hex bytes and printable characters, 16 bytes per line
+    Hexdump,
+    /// utf8 when valid, base64 otherwise
+    Auto,
+}
+
+/// bytes rendered with an encoding, in JSON the non-utf8 ones are tagged as `{"hex": "..."}`
+#[derive(Debug, PartialEq)]
+pub enum KafkyEncodedData {
+    Utf8(String),
+    Hex(String),
+    Base64(String),
+    Hexdump(String),
+    /// decoded with a schema, kept as is in JSON
+    Json(serde_json::Value),
+}
+
+impl KafkyEncoding {
+    pub fn encode(&self, data: &[u8]) -> KafkyEncodedData {
+        match self {
+            KafkyEncoding::Utf8 => {
+                KafkyEncodedData::Utf8(String::from_utf8_lossy(data).to_string())
+            }
+            KafkyEncoding::Hex => {
+                KafkyEncodedData::Hex(data.iter().fold(String::new(), |mut hex, byte| {
+                    let _ = write!(hex, "{:02x}", byte);
+                    hex
+                }))
+            }
+            KafkyEncoding::Base64 => KafkyEncodedData::Base64(base64::encode(data)),
+            KafkyEncoding::Hexdump => KafkyEncodedData::Hexdump(hexdump(data)),
+            KafkyEncoding::Auto => match std::str::from_utf8(data) {
+                Ok(text) => KafkyEncodedData::Utf8(text.to_string()),
+                Err(_) => KafkyEncoding::Base64.encode(data),
+            },
+        }
+    }
+}
+
+fn hexdump(data: &[u8]) -> String {
+    let mut dump = String::new();
+    for (line, chunk) in data.chunks(16).enumerate() {
+        let _ = write!(dump, "{:08x} ", line * 16);
+        for idx in 0..16 {
+            match chunk.get(idx) {
+                Some(byte) => {
+                    let _ = write!(dump, " {:02x}", byte);
+                }
+                None => dump.push_str("   "),
+            }
+        }
+        dump.push_str("  |");
+        dump.extend(chunk.iter().map(|byte| {
+            if byte.is_ascii_graphic() || *byte == b' ' {
+                *byte as char
+            } else {
+                '.'
+            }
+        }));
+        dump.push_str("|\n");
+    }
+    dump.pop();
+    dump
+}
+
+impl Display for KafkyEncodedData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KafkyEncodedData::Utf8(data)
+            | KafkyEncodedData::Hex(data)
+            | KafkyEncodedData::Base64(data)
+            | KafkyEncodedData::Hexdump(data) => f.write_str(data),
+            KafkyEncodedData::Json(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl Serialize for KafkyEncodedData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (tag, data) = match self {
+            KafkyEncodedData::Utf8(data) => return serializer.serialize_str(data),
+            KafkyEncodedData::Json(value) => return value.serialize(serializer),
+            KafkyEncodedData::Hex(data) => ("hex", data),
+            KafkyEncodedData::Base64(data) => ("base64", data),
+            KafkyEncodedData::Hexdump(data) => ("hexdump", data),
+        };
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(tag, data)?;
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn encode_test() {
+        let binary = [0x00u8, 0xff, b'k', b'a'];
+        assert_eq!(
+            KafkyEncoding::Hex.encode(&binary),
+            KafkyEncodedData::Hex("00ff6b61".to_string())
+        );
+        assert_eq!(
+            KafkyEncoding::Auto.encode(&binary),
+            KafkyEncodedData::Base64("AP9rYQ==".to_string())
+        );
+        assert_eq!(
+            KafkyEncoding::Auto.encode(b"kafky"),
+            KafkyEncodedData::Utf8("kafky".to_string())
+        );
+        assert_eq!(
+            KafkyEncoding::Hexdump.encode(b"kafky\n").to_string(),
+            "00000000  6b 61 66 6b 79 0a                                |kafky.|"
+        );
+        assert_eq!(
+            serde_json::to_string(&KafkyEncoding::Hex.encode(&binary)).unwrap(),
+            r#"{"hex":"00ff6b61"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&KafkyEncoding::Utf8.encode(b"kafky")).unwrap(),
+            r#""kafky""#
+        );
+        assert_eq!(KafkyEncoding::from_str("base64"), Ok(KafkyEncoding::Base64));
+    }
+}
@@ -0,0 +1,347 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use apache_avro::Schema;
+use prost_reflect::MessageDescriptor;
+use strum_macros::{Display, EnumString, EnumVariantNames};
+
+use crate::client::encoding::{KafkyEncodedData, KafkyEncoding};
+use crate::client::protobuf;
+use crate::client::schema_registry::{
+    split_wire_format, wire_format, KafkySchemaRegistryClient, KafkySchemaType,
+};
+use crate::KafkyError;
+
+/// serialization format of the keys and values
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, Display, EnumVariantNames)]
+#[strum(serialize_all = "lowercase")]
+pub enum KafkyFormat {
+    /// bytes as they are, rendered with an encoding when consumed
+    Raw,
+    /// Confluent wire format, the schema comes from the schema registry
+    Avro,
+    /// plain or Confluent wire format, the message type comes from local descriptors
+    Protobuf,
+    /// JSON validated against the JSON Schema of the subject when produced
+    Json,
+}
+
+/// decodes the consumed keys or values, the schemas are parsed once per id
+pub struct KafkyDecoder<'a> {
+    format: KafkyFormat,
+    encoding: KafkyEncoding,
+    registry: Option<&'a KafkySchemaRegistryClient>,
+    avro_schemas: RefCell<HashMap<u32, Rc<Schema>>>,
+    message: Option<MessageDescriptor>,
+}
+
+impl<'a> KafkyDecoder<'a> {
+    pub fn new(
+        format: KafkyFormat,
+        encoding: KafkyEncoding,
+        registry: Option<&'a KafkySchemaRegistryClient>,
+    ) -> Self {
+        KafkyDecoder {
+            format,
+            encoding,
+            registry,
+            avro_schemas: RefCell::new(HashMap::new()),
+            message: None,
+        }
+    }
+
+    /// protobuf message type of the keys or values
+    pub fn with_message(mut self, message: Option<MessageDescriptor>) -> Self {
+        self.message = message;
+        self
+    }
+
+    pub fn decode(&self, data: &[u8]) -> Result<KafkyEncodedData, String> {
+        match self.format {
+            KafkyFormat::Raw => Ok(self.encoding.encode(data)),
+            KafkyFormat::Avro => {
+                let (id, mut datum) = split_wire_format(data)?;
+                let schema = self.avro_schema(id)?;
+                let value = apache_avro::from_avro_datum(&schema, &mut datum, None)
+                    .map_err(|e| e.to_string())?;
+                serde_json::Value::try_from(value)
+                    .map(KafkyEncodedData::Json)
+                    .map_err(|e| e.to_string())
+            }
+            KafkyFormat::Protobuf => {
+                let message = self.message.as_ref().ok_or("no message type")?;
+                protobuf::decode(message, data).map(KafkyEncodedData::Json)
+            }
+            KafkyFormat::Json => {
+                // JSON never starts with a zero byte, the wire format header is optional
+                let json = split_wire_format(data).map_or(data, |(_, json)| json);
+                serde_json::from_slice(json)
+                    .map(KafkyEncodedData::Json)
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    fn avro_schema(&self, id: u32) -> Result<Rc<Schema>, String> {
+        if let Some(schema) = self.avro_schemas.borrow().get(&id) {
+            return Ok(schema.clone());
+        }
+        let registry = self.registry.ok_or("no schema registry")?;
+        let registered = registry.schema_by_id(id).map_err(|e| e.to_string())?;
+        if registered.schema_type != KafkySchemaType::Avro {
+            return Err(format!("the schema {} is not an avro schema", id));
+        }
+        let schema = Rc::new(Schema::parse_str(&registered.schema).map_err(|e| e.to_string())?);
+        self.avro_schemas.borrow_mut().insert(id, schema.clone());
+        Ok(schema)
+    }
+}
+
+/// encodes the produced keys or values given as text
+pub enum KafkyEncoder {
+    Raw,
+    Avro {
+        id: u32,
+        schema: Schema,
+    },
+    Protobuf {
+        message: MessageDescriptor,
+        id: Option<u32>,
+    },
+    Json {
+        validator: jsonschema::Validator,
+        id: Option<u32>,
+    },
+}
+
+impl KafkyEncoder {
+    /// the schema is the given version of the subject, the latest when `None`;
+    /// avro is always in the wire format, protobuf and json only when `wire_format`
+    pub fn new(
+        format: KafkyFormat,
+        registry: Option<&KafkySchemaRegistryClient>,
+        subject: &str,
+        version: Option<u32>,
+        message: Option<MessageDescriptor>,
+        wire_format: bool,
+    ) -> Result<Self, KafkyError> {
+        let registered = |schema_type: KafkySchemaType| {
+            let registry = registry
+                .ok_or_else(|| KafkyError::SchemaRegistryError("no schema registry".to_string()))?;
+            let registered = registry.subject_schema(subject, version)?;
+            if registered.schema_type != schema_type {
+                return Err(KafkyError::SchemaRegistryError(format!(
+                    "the schema of the subject {} is not registered as {:?}",
+                    subject, schema_type
+                )));
+            }
+            Ok(registered)
+        };
+        match format {
+            KafkyFormat::Raw => Ok(KafkyEncoder::Raw),
+            KafkyFormat::Avro => {
+                let registered = registered(KafkySchemaType::Avro)?;
+                let schema = Schema::parse_str(&registered.schema).map_err(|e| {
+                    KafkyError::SchemaRegistryError(format!("subject {}: {}", subject, e))
+                })?;
+                Ok(KafkyEncoder::Avro {
+                    id: registered.id,
+                    schema,
+                })
+            }
+            KafkyFormat::Protobuf => Ok(KafkyEncoder::Protobuf {
+                message: message
+                    .ok_or_else(|| KafkyError::ProtobufError("no message type".to_string()))?,
+                id: match wire_format {
+                    true => Some(registered(KafkySchemaType::Protobuf)?.id),
+                    false => None,
+                },
+            }),
+            KafkyFormat::Json => {
+                let registered = registered(KafkySchemaType::Json)?;
+                let invalid_schema = |e: String| {
+                    KafkyError::SchemaRegistryError(format!("subject {}: {}", subject, e))
+                };
+                let schema = serde_json::from_str(&registered.schema)
+                    .map_err(|e| invalid_schema(e.to_string()))?;
+                Ok(KafkyEncoder::Json {
+                    validator: jsonschema::validator_for(&schema)
+                        .map_err(|e| invalid_schema(e.to_string()))?,
+                    id: Some(registered.id).filter(|_| wire_format),
+                })
+            }
+        }
+    }
+
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, String> {
+        match self {
+            KafkyEncoder::Raw => Ok(text.as_bytes().to_vec()),
+            KafkyEncoder::Avro { id, schema } => {
+                let json: serde_json::Value =
+                    serde_json::from_str(text).map_err(|e| e.to_string())?;
+                let value = apache_avro::types::Value::from(json)
+                    .resolve(schema)
+                    .map_err(|e| e.to_string())?;
+                let datum = apache_avro::to_avro_datum(schema, value).map_err(|e| e.to_string())?;
+                Ok(wire_format(*id, &datum))
+            }
+            KafkyEncoder::Protobuf { message, id } => protobuf::encode(message, text, *id),
+            KafkyEncoder::Json { validator, id } => {
+                let json: serde_json::Value =
+                    serde_json::from_str(text).map_err(|e| e.to_string())?;
+                let errors: Vec<String> = validator
+                    .iter_errors(&json)
+                    .map(|e| {
+                        let path = e.instance_path.to_string();
+                        format!(
+                            "{} at {} (schema {})",
+                            e,
+                            if path.is_empty() { "the root" } else { &path },
+                            e.schema_path
+                        )
+                    })
+                    .collect();
+                if !errors.is_empty() {
+                    return Err(errors.join(", "));
+                }
+                Ok(match id {
+                    Some(id) => wire_format(*id, text.as_bytes()),
+                    None => text.as_bytes().to_vec(),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::schema_registry::tests::mock_registry;
+    use crate::config::KafkySchemaRegistry;
+    use serde_json::json;
+
+    const ORDER_SCHEMA: &str = r#"{"type":"record","name":"Order","fields":[{"name":"id","type":"long"},{"name":"status","type":"string"},{"name":"note","type":["null","string"],"default":null}]}"#;
+
+    #[test]
+    fn avro_test() -> Result<(), KafkyError> {
+        let schema = serde_json::to_string(ORDER_SCHEMA).unwrap();
+        let url = mock_registry(
+            vec![
+                (
+                    "/subjects/orders-value/versions/2".to_string(),
+                    format!(r#"{{"id":12,"version":2,"schema":{}}}"#, schema),
+                ),
+                (
+                    "/schemas/ids/12".to_string(),
+                    format!(r#"{{"schema":{}}}"#, schema),
+                ),
+            ],
+            2,
+        );
+        let registry = KafkySchemaRegistryClient::new(
+            &KafkySchemaRegistry {
+                url,
+                username: None,
+                password: None,
+            },
+            None,
+        );
+
+        let encoder = KafkyEncoder::new(
+            KafkyFormat::Avro,
+            Some(&registry),
+            "orders-value",
+            Some(2),
+            None,
+            false,
+        )?;
+        let encoded = encoder
+            .encode(r#"{"id": 42, "status": "FAILED", "note": "retry"}"#)
+            .unwrap();
+        assert_eq!(&encoded[..5], &[0, 0, 0, 0, 12]);
+        assert!(encoder.encode(r#"{"id": "42"}"#).is_err());
+
+        // a new decoder fetches the schema by id
+        let decoder = KafkyDecoder::new(KafkyFormat::Avro, KafkyEncoding::Utf8, Some(&registry));
+        assert_eq!(
+            decoder.decode(&encoded).unwrap(),
+            KafkyEncodedData::Json(json!({"id": 42, "status": "FAILED", "note": "retry"}))
+        );
+        assert!(decoder.decode(b"not avro").is_err());
+
+        let raw = KafkyDecoder::new(KafkyFormat::Raw, KafkyEncoding::Hex, None);
+        assert_eq!(
+            raw.decode(b"ok").unwrap(),
+            KafkyEncodedData::Hex("6f6b".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn json_schema_test() -> Result<(), KafkyError> {
+        let schema = serde_json::to_string(
+            &json!({
+                "type": "object",
+                "properties": {
+                    "order": {
+                        "type": "object",
+                        "properties": {"status": {"enum": ["OK", "FAILED"]}},
+                        "required": ["status"]
+                    }
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let url = mock_registry(
+            vec![(
+                "/subjects/orders-value/versions/latest".to_string(),
+                format!(
+                    r#"{{"id":21,"version":1,"schemaType":"JSON","schema":{}}}"#,
+                    schema
+                ),
+            )],
+            2,
+        );
+        let registry = KafkySchemaRegistryClient::new(
+            &KafkySchemaRegistry {
+                url,
+                username: None,
+                password: None,
+            },
+            None,
+        );
+        let encoder = KafkyEncoder::new(
+            KafkyFormat::Json,
+            Some(&registry),
+            "orders-value",
+            None,
+            None,
+            true,
+        )?;
+        let valid = r#"{"order": {"status": "FAILED"}}"#;
+        let encoded = encoder.encode(valid).unwrap();
+        assert_eq!(&encoded[..5], &[0, 0, 0, 0, 21]);
+        assert_eq!(&encoded[5..], valid.as_bytes());
+
+        let error = encoder
+            .encode(r#"{"order": {"status": "LOST"}}"#)
+            .unwrap_err();
+        assert!(error.contains("at /order/status"), "{}", error);
+        assert!(error.contains("(schema /properties/order/properties/status/enum)"));
+        let error = encoder.encode(r#"{"order": {}}"#).unwrap_err();
+        assert!(error.contains("at /order"), "{}", error);
+        assert!(encoder
+            .encode(r#"{"order": "#)
+            .unwrap_err()
+            .contains("line 1"));
+
+        let decoder = KafkyDecoder::new(KafkyFormat::Json, KafkyEncoding::Utf8, None);
+        assert_eq!(
+            decoder.decode(&encoded).unwrap(),
+            KafkyEncodedData::Json(json!({"order": {"status": "FAILED"}}))
+        );
+        Ok(())
+    }
+}
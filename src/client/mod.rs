@@ -1,7 +1,13 @@
 mod admin;
 pub mod consumer;
 pub mod consumer_group;
+mod context;
+pub mod encoding;
+pub mod format;
 pub mod kafky_client;
 mod metadata;
+mod oauth;
 mod offset;
 mod producer;
+pub mod protobuf;
+pub mod schema_registry;
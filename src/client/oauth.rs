@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{debug, warn};
+use rdkafka::admin::AdminClient;
+use rdkafka::bindings::{rd_kafka_oauthbearer_set_token, rd_kafka_oauthbearer_set_token_failure};
+use rdkafka::client::{Client, OAuthToken};
+use rdkafka::consumer::{BaseConsumer, Consumer, StreamConsumer};
+use rdkafka::producer::{BaseProducer, Producer};
+use rdkafka::types::RDKafkaRespErr;
+use serde::Deserialize;
+
+use crate::client::context::KafkyClientContext;
+use crate::config::KafkyOAuthBearerCredential;
+use crate::KafkyError;
+
+/// token lifetime used when the token endpoint doesn't return `expires_in`
+const DEFAULT_TOKEN_LIFETIME_SEC: u64 = 3600;
+
+/// delay before fetching a token again after a failure
+const TOKEN_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtClaims {
+    sub: Option<String>,
+}
+
+/// Fetches OAUTHBEARER tokens from an OAuth 2 token endpoint using the client credentials grant
+#[derive(Debug, Clone)]
+pub struct KafkyOAuthTokenProvider {
+    client_id: String,
+    client_secret: String,
+    token_endpoint_url: String,
+    scope: Option<String>,
+    extensions: BTreeMap<String, String>,
+    /// replaces 80% of the token lifetime, for the tests
+    refresh_interval: Option<Duration>,
+}
+
+impl KafkyOAuthTokenProvider {
+    pub fn new(credential: &KafkyOAuthBearerCredential, client_secret: String) -> Self {
+        KafkyOAuthTokenProvider {
+            client_id: credential.client_id.clone(),
+            client_secret,
+            token_endpoint_url: credential.token_endpoint_url.clone(),
+            scope: credential.scope.clone(),
+            extensions: credential.extensions.clone(),
+            refresh_interval: None,
+        }
+    }
+
+    #[cfg(test)]
+    fn with_refresh_interval(self, refresh_interval: Duration) -> Self {
+        KafkyOAuthTokenProvider {
+            refresh_interval: Some(refresh_interval),
+            ..self
+        }
+    }
+
+    pub fn token(&self) -> Result<OAuthToken, KafkyError> {
+        debug!("requesting oauth token to {}", &self.token_endpoint_url);
+        let mut form = vec![
+            ("grant_type", "client_credentials"),
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+        ];
+        if let Some(scope) = &self.scope {
+            form.push(("scope", scope.as_str()));
+        }
+        let response: TokenResponse = ureq::post(&self.token_endpoint_url)
+            .send_form(&form)
+            .map_err(|e| KafkyError::OAuthTokenError(e.to_string()))?
+            .into_json()
+            .map_err(|e| KafkyError::OAuthTokenError(e.to_string()))?;
+
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time before unix epoch")
+            .as_millis() as i64;
+        let lifetime_sec = response.expires_in.unwrap_or(DEFAULT_TOKEN_LIFETIME_SEC);
+        let principal_name =
+            Self::jwt_subject(&response.access_token).unwrap_or_else(|| self.client_id.clone());
+        debug!(
+            "oauth token for {} expires in {}s",
+            &principal_name, lifetime_sec
+        );
+        Ok(OAuthToken {
+            token: response.access_token,
+            principal_name,
+            lifetime_ms: now_ms + (lifetime_sec * 1000) as i64,
+        })
+    }
+
+    /// sets the token of the client, then refreshes it at 80% of its lifetime until the client
+    /// is dropped.
+    /// librdkafka's own OIDC method needs a build with curl, which the bundled one is not,
+    /// and has no SASL extensions. rdkafka's `ClientContext::generate_oauth_token` is called on
+    /// the refresh events of the main queue only, never polled by the admin client, so its
+    /// requests would wait for a token forever
+    pub fn keep_token<T: KafkyOAuthClient>(&self, client: &Arc<T>) -> Result<(), KafkyError> {
+        let token = self.token()?;
+        self.set_token(client.native_client(), &token)?;
+        let mut refresh_in = self.refresh_in(&token);
+        let provider = self.clone();
+        let client = Arc::downgrade(client);
+        thread::Builder::new()
+            .name("oauth token refresh".to_string())
+            .spawn(move || loop {
+                thread::sleep(refresh_in);
+                let client = match client.upgrade() {
+                    Some(client) => client,
+                    None => break,
+                };
+                refresh_in = match provider.token().and_then(|token| {
+                    provider.set_token(client.native_client(), &token)?;
+                    Ok(provider.refresh_in(&token))
+                }) {
+                    Ok(refresh_in) => refresh_in,
+                    Err(e) => {
+                        warn!("oauth token refresh failed: {}", e);
+                        Self::set_token_failure(client.native_client(), &e.to_string());
+                        TOKEN_RETRY_INTERVAL
+                    }
+                };
+            })?;
+        Ok(())
+    }
+
+    fn refresh_in(&self, token: &OAuthToken) -> Duration {
+        if let Some(refresh_interval) = self.refresh_interval {
+            return refresh_interval;
+        }
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time before unix epoch")
+            .as_millis() as i64;
+        Duration::from_millis(((token.lifetime_ms - now_ms).max(0) * 8 / 10) as u64)
+    }
+
+    /// sets the token with the SASL extensions of the credential
+    fn set_token(
+        &self,
+        client: &Client<KafkyClientContext>,
+        token: &OAuthToken,
+    ) -> Result<(), KafkyError> {
+        let c_string =
+            |s: &str| CString::new(s).map_err(|e| KafkyError::OAuthTokenError(e.to_string()));
+        let (value, principal_name) = (c_string(&token.token)?, c_string(&token.principal_name)?);
+        // keys and values alternate
+        let extensions = self
+            .extensions
+            .iter()
+            .flat_map(|(key, value)| [key, value])
+            .map(|s| c_string(s))
+            .collect::<Result<Vec<CString>, KafkyError>>()?;
+        let mut extension_ptrs: Vec<*const c_char> =
+            extensions.iter().map(|s| s.as_ptr()).collect();
+        let mut errstr = [0 as c_char; 512];
+        // librdkafka copies the token, the strings only need to outlive the call
+        let code = unsafe {
+            rd_kafka_oauthbearer_set_token(
+                client.native_ptr(),
+                value.as_ptr(),
+                token.lifetime_ms,
+                principal_name.as_ptr(),
+                if extension_ptrs.is_empty() {
+                    ptr::null_mut()
+                } else {
+                    extension_ptrs.as_mut_ptr()
+                },
+                extension_ptrs.len(),
+                errstr.as_mut_ptr(),
+                errstr.len(),
+            )
+        };
+        if code != RDKafkaRespErr::RD_KAFKA_RESP_ERR_NO_ERROR {
+            let error = unsafe { CStr::from_ptr(errstr.as_ptr()) };
+            return Err(KafkyError::OAuthTokenError(
+                error.to_string_lossy().into_owned(),
+            ));
+        }
+        debug!("oauth token set for {}", &token.principal_name);
+        Ok(())
+    }
+
+    /// the connections waiting for a token fail with the error
+    fn set_token_failure(client: &Client<KafkyClientContext>, error: &str) {
+        let error = CString::new(error.replace('\0', "")).unwrap_or_default();
+        unsafe { rd_kafka_oauthbearer_set_token_failure(client.native_ptr(), error.as_ptr()) };
+    }
+
+    /// `sub` claim of a JWT access token, opaque tokens don't have it
+    fn jwt_subject(token: &str) -> Option<String> {
+        let payload = token.split('.').nth(1)?;
+        let decoded = base64::decode_config(payload, base64::URL_SAFE_NO_PAD).ok()?;
+        serde_json::from_slice::<JwtClaims>(&decoded).ok()?.sub
+    }
+}
+
+/// rdkafka clients whose OAUTHBEARER token is kept by a [`KafkyOAuthTokenProvider`]
+pub trait KafkyOAuthClient: Send + Sync + 'static {
+    fn native_client(&self) -> &Client<KafkyClientContext>;
+}
+
+impl KafkyOAuthClient for BaseProducer<KafkyClientContext> {
+    fn native_client(&self) -> &Client<KafkyClientContext> {
+        self.client()
+    }
+}
+
+impl KafkyOAuthClient for BaseConsumer<KafkyClientContext> {
+    fn native_client(&self) -> &Client<KafkyClientContext> {
+        self.client()
+    }
+}
+
+impl KafkyOAuthClient for StreamConsumer<KafkyClientContext> {
+    fn native_client(&self) -> &Client<KafkyClientContext> {
+        self.client()
+    }
+}
+
+impl KafkyOAuthClient for AdminClient<KafkyClientContext> {
+    fn native_client(&self) -> &Client<KafkyClientContext> {
+        self.inner()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::KafkySecret;
+    use rdkafka::ClientConfig;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// token endpoint answering `requests` requests with `body`, yields the received forms
+    pub(crate) fn token_endpoint_stub(
+        body: &'static str,
+        requests: usize,
+    ) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/token", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut forms = vec![];
+            for stream in listener.incoming().take(requests) {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(len) = line.to_lowercase().strip_prefix("content-length:") {
+                        content_length = len.trim().parse().unwrap();
+                    }
+                }
+                let mut request_body = vec![0; content_length];
+                reader.read_exact(&mut request_body).unwrap();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+                forms.push(String::from_utf8(request_body).unwrap());
+            }
+            forms
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn client_credentials_token_test() -> Result<(), KafkyError> {
+        // {"sub":"kafky-svc"}
+        let (url, stub) = token_endpoint_stub(
+            r#"{"access_token":"eyJhbGciOiJub25lIn0.eyJzdWIiOiJrYWZreS1zdmMifQ.","token_type":"bearer","expires_in":60}"#,
+            1,
+        );
+        let provider = KafkyOAuthTokenProvider::new(
+            &KafkyOAuthBearerCredential {
+                client_id: "kafky".to_string(),
+                client_secret: KafkySecret::from("secret"),
+                token_endpoint_url: url,
+                scope: Some("kafka".to_string()),
+                extensions: BTreeMap::from([
+                    ("logicalCluster".to_string(), "lkc-42".to_string()),
+                    ("identityPoolId".to_string(), "pool-7".to_string()),
+                ]),
+            },
+            "secret".to_string(),
+        );
+        let token = provider.token()?;
+        let form = stub.join().unwrap().remove(0);
+        assert!(form.contains("grant_type=client_credentials"));
+        assert!(form.contains("client_id=kafky"));
+        assert!(form.contains("client_secret=secret"));
+        assert!(form.contains("scope=kafka"));
+        assert_eq!(token.principal_name, "kafky-svc");
+        assert!(token.token.starts_with("eyJhbGciOiJub25lIn0."));
+
+        // librdkafka validates the extensions set with the token
+        let producer: BaseProducer<KafkyClientContext> = ClientConfig::new()
+            .set("log_level", "0")
+            .set("security.protocol", "sasl_plaintext")
+            .set("sasl.mechanism", "OAUTHBEARER")
+            .create_with_context(KafkyClientContext)?;
+        provider.set_token(producer.client(), &token)?;
+        let reserved = KafkyOAuthTokenProvider {
+            extensions: BTreeMap::from([("auth".to_string(), "x".to_string())]),
+            ..provider
+        };
+        assert!(matches!(
+            reserved.set_token(producer.client(), &token),
+            Err(KafkyError::OAuthTokenError(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn keep_token_test() -> Result<(), KafkyError> {
+        let (url, stub) = token_endpoint_stub(r#"{"access_token":"token","expires_in":3600}"#, 3);
+        let provider = KafkyOAuthTokenProvider::new(
+            &KafkyOAuthBearerCredential {
+                client_id: "kafky".to_string(),
+                client_secret: KafkySecret::from("secret"),
+                token_endpoint_url: url,
+                scope: None,
+                extensions: BTreeMap::new(),
+            },
+            "secret".to_string(),
+        )
+        .with_refresh_interval(Duration::from_millis(10));
+        // no broker to connect to, the token is set without any connection
+        let producer: Arc<BaseProducer<KafkyClientContext>> = Arc::new(
+            ClientConfig::new()
+                .set("log_level", "0")
+                .set("security.protocol", "sasl_plaintext")
+                .set("sasl.mechanism", "OAUTHBEARER")
+                .create_with_context(KafkyClientContext)?,
+        );
+        provider.keep_token(&producer)?;
+        // the initial token and two refreshes
+        assert_eq!(stub.join().unwrap().len(), 3);
+        drop(producer);
+        Ok(())
+    }
+}
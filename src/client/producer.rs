@@ -1,30 +1,44 @@
 use log::debug;
+use rdkafka::message::{Header, OwnedHeaders};
 use rdkafka::producer::{BaseRecord, Producer};
 use std::time::Duration;
 
+use crate::client::consumer::KafkyHeader;
 use crate::{KafkyClient, KafkyError};
 
 impl<'a> KafkyClient<'a> {
     pub fn produce(
         &self,
         topic: &str,
-        key: Option<String>,
-        payload: String,
+        key: Option<Vec<u8>>,
+        payload: Vec<u8>,
+        headers: &[KafkyHeader],
     ) -> Result<(), KafkyError> {
         debug!(
-            "sending message to {}, key:{:?}, payload:{}",
-            &topic, &key, &payload
+            "sending message to {}, key:{:?}, payload:{}, headers:{:?}",
+            &topic,
+            key.as_ref().map(|key| String::from_utf8_lossy(key)),
+            String::from_utf8_lossy(&payload),
+            headers
         );
-        let mut record: BaseRecord<String, String> = BaseRecord::to(topic);
-        if key.is_some() {
-            record = record.key(key.as_ref().unwrap());
+        let mut record: BaseRecord<Vec<u8>, Vec<u8>> = BaseRecord::to(topic);
+        if let Some(key) = key.as_ref() {
+            record = record.key(key);
         }
         record = record.payload(&payload);
+        if !headers.is_empty() {
+            record = record.headers(headers.iter().fold(OwnedHeaders::new(), |owned, header| {
+                owned.insert(Header {
+                    key: &header.key,
+                    value: header.value.as_ref(),
+                })
+            }));
+        }
         let producer = self.get_producer()?;
         match producer.send(record) {
             Ok(_) => {
                 debug!("Message sent");
-                producer.flush(Duration::from_millis(1000));
+                producer.flush(Duration::from_millis(1000))?;
                 Ok(())
             }
             Err(err) => Err(err.0.into()),
@@ -0,0 +1,215 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use prost::encoding::{decode_varint, encode_varint};
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor};
+
+use crate::client::schema_registry::{split_wire_format, wire_format};
+use crate::KafkyError;
+
+/// message types compiled from .proto files or loaded from descriptor sets
+pub struct KafkyProtoSchema {
+    pool: DescriptorPool,
+}
+
+impl KafkyProtoSchema {
+    /// every .proto file below the proto paths is compiled, the proto paths are the import roots
+    pub fn load(proto_paths: &[PathBuf], descriptor_sets: &[PathBuf]) -> Result<Self, KafkyError> {
+        let mut pool = DescriptorPool::new();
+        if !proto_paths.is_empty() {
+            let mut files = vec![];
+            for proto_path in proto_paths {
+                Self::proto_files(proto_path, &mut files)?;
+            }
+            let file_descriptor_set = protox::compile(&files, proto_paths)
+                .map_err(|e| KafkyError::ProtobufError(e.to_string()))?;
+            pool.add_file_descriptor_set(file_descriptor_set)
+                .map_err(|e| KafkyError::ProtobufError(e.to_string()))?;
+        }
+        for descriptor_set in descriptor_sets {
+            let bytes = fs::read(descriptor_set)?;
+            pool.decode_file_descriptor_set(bytes.as_slice())
+                .map_err(|e| {
+                    KafkyError::ProtobufError(format!("{}: {}", descriptor_set.display(), e))
+                })?;
+        }
+        Ok(KafkyProtoSchema { pool })
+    }
+
+    fn proto_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), KafkyError> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                Self::proto_files(&path, files)?;
+            } else if path.extension().is_some_and(|ext| ext == "proto") {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn message(&self, message_type: &str) -> Result<MessageDescriptor, KafkyError> {
+        self.pool.get_message_by_name(message_type).ok_or_else(|| {
+            KafkyError::ProtobufError(format!("message type {} not found", message_type))
+        })
+    }
+}
+
+/// decodes plain protobuf or the Confluent wire format, recognized by its magic byte since
+/// a protobuf message never starts with a zero byte; there the message indexes select the
+/// message in the file declaring `message`
+pub fn decode(message: &MessageDescriptor, data: &[u8]) -> Result<serde_json::Value, String> {
+    let (message, mut data) = match split_wire_format(data) {
+        Ok((_, mut data)) => {
+            let indexes = read_message_indexes(&mut data)?;
+            (indexed_message(message, &indexes)?, data)
+        }
+        Err(_) => (message.clone(), data),
+    };
+    let decoded = DynamicMessage::decode(message, &mut data).map_err(|e| e.to_string())?;
+    serde_json::to_value(&decoded).map_err(|e| e.to_string())
+}
+
+/// encodes the JSON text, in the Confluent wire format with the schema id when given
+pub fn encode(
+    message: &MessageDescriptor,
+    text: &str,
+    schema_id: Option<u32>,
+) -> Result<Vec<u8>, String> {
+    let mut deserializer = serde_json::Deserializer::from_str(text);
+    let decoded = DynamicMessage::deserialize(message.clone(), &mut deserializer)
+        .map_err(|e| e.to_string())?;
+    deserializer.end().map_err(|e| e.to_string())?;
+    let data = decoded.encode_to_vec();
+    Ok(match schema_id {
+        Some(id) => {
+            let mut indexed = vec![];
+            write_message_indexes(&message_indexes(message), &mut indexed);
+            indexed.extend_from_slice(&data);
+            wire_format(id, &indexed)
+        }
+        None => data,
+    })
+}
+
+/// position of the message in its file, e.g. `[1, 0]` for the first nested message of the second one
+fn message_indexes(message: &MessageDescriptor) -> Vec<i32> {
+    // the path alternates the field number of the message list and the index in it
+    message.path().iter().skip(1).step_by(2).copied().collect()
+}
+
+fn indexed_message(
+    message: &MessageDescriptor,
+    indexes: &[i32],
+) -> Result<MessageDescriptor, String> {
+    let not_found = || format!("no message at the indexes {:?}", indexes);
+    let (first, nested) = indexes.split_first().ok_or_else(not_found)?;
+    let mut indexed = message
+        .parent_file()
+        .messages()
+        .nth(*first as usize)
+        .ok_or_else(not_found)?;
+    for index in nested {
+        let child = indexed.child_messages().nth(*index as usize);
+        indexed = child.ok_or_else(not_found)?;
+    }
+    Ok(indexed)
+}
+
+fn zigzag(value: i32) -> u64 {
+    ((value << 1) ^ (value >> 31)) as u32 as u64
+}
+
+fn unzigzag(value: u64) -> i32 {
+    let value = value as u32;
+    ((value >> 1) as i32) ^ -((value & 1) as i32)
+}
+
+/// zigzag varint count followed by the indexes, `[0]` is written as a single zero
+fn write_message_indexes(indexes: &[i32], buf: &mut Vec<u8>) {
+    if indexes == [0] {
+        buf.push(0);
+        return;
+    }
+    encode_varint(zigzag(indexes.len() as i32), buf);
+    for index in indexes {
+        encode_varint(zigzag(*index), buf);
+    }
+}
+
+fn read_message_indexes(data: &mut &[u8]) -> Result<Vec<i32>, String> {
+    let invalid = |e: prost::DecodeError| format!("invalid message indexes: {}", e);
+    let count = unzigzag(decode_varint(data).map_err(invalid)?);
+    if count == 0 {
+        return Ok(vec![0]);
+    }
+    (0..count)
+        .map(|_| decode_varint(data).map(unzigzag).map_err(invalid))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use serde_json::json;
+
+    #[test]
+    fn protobuf_test() -> Result<(), KafkyError> {
+        let proto_dir = tempfile::tempdir()?;
+        fs::create_dir(proto_dir.path().join("shop"))?;
+        fs::write(
+            proto_dir.path().join("shop").join("order.proto"),
+            indoc! {r#"
+                syntax = "proto3";
+                package shop;
+
+                message Customer {
+                  string name = 1;
+                }
+
+                message Order {
+                  message Line {
+                    string sku = 1;
+                    int32 quantity = 2;
+                  }
+                  int64 id = 1;
+                  string status = 2;
+                  repeated Line lines = 3;
+                }
+            "#},
+        )?;
+        let schema = KafkyProtoSchema::load(&[proto_dir.path().to_path_buf()], &[])?;
+        let order = schema.message("shop.Order")?;
+        let line = schema.message("shop.Order.Line")?;
+        assert_eq!(message_indexes(&order), vec![1]);
+        assert_eq!(message_indexes(&line), vec![1, 0]);
+        assert!(schema.message("shop.Invoice").is_err());
+
+        let text = r#"{"id": "42", "status": "FAILED", "lines": [{"sku": "A1", "quantity": 2}]}"#;
+        let expected =
+            json!({"id": "42", "status": "FAILED", "lines": [{"sku": "A1", "quantity": 2}]});
+        let plain = encode(&order, text, None).unwrap();
+        assert_eq!(decode(&order, &plain).unwrap(), expected);
+        assert!(encode(&order, r#"{"unknown": 1}"#, None).is_err());
+
+        // the wire format indexes win over the message type
+        let wired = encode(&line, r#"{"sku": "B2"}"#, Some(5)).unwrap();
+        assert_eq!(&wired[..7], &[0, 0, 0, 0, 5, 4, 2]);
+        assert_eq!(decode(&order, &wired).unwrap(), json!({"sku": "B2"}));
+        let customer = schema.message("shop.Customer")?;
+        let wired = encode(&customer, r#"{"name": "kafky"}"#, Some(5)).unwrap();
+        assert_eq!(&wired[..6], &[0, 0, 0, 0, 5, 0]);
+        assert_eq!(decode(&order, &wired).unwrap(), json!({"name": "kafky"}));
+
+        let descriptor_set = proto_dir.path().join("order.desc");
+        fs::write(&descriptor_set, schema.pool.encode_to_vec())?;
+        let from_set = KafkyProtoSchema::load(&[], &[descriptor_set])?;
+        assert_eq!(
+            decode(&from_set.message("shop.Order")?, &plain).unwrap(),
+            expected
+        );
+        Ok(())
+    }
+}
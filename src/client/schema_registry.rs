@@ -0,0 +1,197 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use log::debug;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::config::KafkySchemaRegistry;
+use crate::KafkyError;
+
+/// first byte of the Confluent wire format, followed by the big endian schema id
+const MAGIC_BYTE: u8 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum KafkySchemaType {
+    #[default]
+    Avro,
+    Protobuf,
+    Json,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KafkyRegisteredSchema {
+    /// missing in the responses by id
+    #[serde(default)]
+    pub id: u32,
+    pub schema: String,
+    #[serde(rename = "schemaType", default)]
+    pub schema_type: KafkySchemaType,
+}
+
+/// Confluent schema registry REST client, the schemas are cached by id
+pub struct KafkySchemaRegistryClient {
+    url: String,
+    authorization: Option<String>,
+    schemas: RefCell<HashMap<u32, Rc<KafkyRegisteredSchema>>>,
+}
+
+impl KafkySchemaRegistryClient {
+    pub fn new(registry: &KafkySchemaRegistry, password: Option<String>) -> Self {
+        KafkySchemaRegistryClient {
+            url: registry.url.trim_end_matches('/').to_string(),
+            authorization: registry.username.as_ref().map(|username| {
+                format!(
+                    "Basic {}",
+                    base64::encode(format!("{}:{}", username, password.unwrap_or_default()))
+                )
+            }),
+            schemas: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, KafkyError> {
+        let url = format!("{}{}", self.url, path);
+        debug!("schema registry request {}", &url);
+        let mut request = ureq::get(&url).set("Accept", "application/vnd.schemaregistry.v1+json");
+        if let Some(authorization) = &self.authorization {
+            request = request.set("Authorization", authorization);
+        }
+        request
+            .call()
+            .map_err(|e| KafkyError::SchemaRegistryError(format!("{}: {}", url, e)))?
+            .into_json()
+            .map_err(|e| KafkyError::SchemaRegistryError(format!("{}: {}", url, e)))
+    }
+
+    pub fn schema_by_id(&self, id: u32) -> Result<Rc<KafkyRegisteredSchema>, KafkyError> {
+        if let Some(schema) = self.schemas.borrow().get(&id) {
+            return Ok(schema.clone());
+        }
+        let mut schema: KafkyRegisteredSchema = self.get(&format!("/schemas/ids/{}", id))?;
+        schema.id = id;
+        let schema = Rc::new(schema);
+        self.schemas.borrow_mut().insert(id, schema.clone());
+        Ok(schema)
+    }
+
+    /// the given version of the subject, the latest when `None`
+    pub fn subject_schema(
+        &self,
+        subject: &str,
+        version: Option<u32>,
+    ) -> Result<Rc<KafkyRegisteredSchema>, KafkyError> {
+        let version = version.map_or("latest".to_string(), |v| v.to_string());
+        let schema: KafkyRegisteredSchema =
+            self.get(&format!("/subjects/{}/versions/{}", subject, version))?;
+        let schema = Rc::new(schema);
+        self.schemas.borrow_mut().insert(schema.id, schema.clone());
+        Ok(schema)
+    }
+}
+
+/// the schema id and the data of a message in the Confluent wire format
+pub fn split_wire_format(data: &[u8]) -> Result<(u32, &[u8]), String> {
+    match data {
+        [MAGIC_BYTE, b0, b1, b2, b3, rest @ ..] => {
+            Ok((u32::from_be_bytes([*b0, *b1, *b2, *b3]), rest))
+        }
+        _ => Err("no schema registry wire format header".to_string()),
+    }
+}
+
+pub fn wire_format(id: u32, data: &[u8]) -> Vec<u8> {
+    let mut message = vec![MAGIC_BYTE];
+    message.extend_from_slice(&id.to_be_bytes());
+    message.extend_from_slice(data);
+    message
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// schema registry answering `GET path` with the JSON body of the route, 404 otherwise,
+    /// it stops after `requests` requests
+    pub(crate) fn mock_registry(routes: Vec<(String, String)>, requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+                let path = request_line.split(' ').nth(1).unwrap_or_default();
+                let (status, body) = match routes.iter().find(|(route, _)| route == path) {
+                    Some((_, body)) => ("200 OK", body.as_str()),
+                    None => ("404 Not Found", r#"{"error_code":40401}"#),
+                };
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn schema_registry_test() -> Result<(), KafkyError> {
+        let url = mock_registry(
+            vec![
+                (
+                    "/subjects/orders-value/versions/latest".to_string(),
+                    r#"{"subject":"orders-value","version":3,"id":7,"schema":"\"string\""}"#
+                        .to_string(),
+                ),
+                (
+                    "/schemas/ids/8".to_string(),
+                    r#"{"schema":"syntax = \"proto3\";","schemaType":"PROTOBUF"}"#.to_string(),
+                ),
+            ],
+            3,
+        );
+        let client = KafkySchemaRegistryClient::new(
+            &KafkySchemaRegistry {
+                url,
+                username: None,
+                password: None,
+            },
+            None,
+        );
+        let latest = client.subject_schema("orders-value", None)?;
+        assert_eq!(latest.id, 7);
+        assert_eq!(latest.schema_type, KafkySchemaType::Avro);
+        // served from the cache
+        assert_eq!(client.schema_by_id(7)?.schema, "\"string\"");
+        let proto = client.schema_by_id(8)?;
+        assert_eq!(proto.schema_type, KafkySchemaType::Protobuf);
+        assert!(matches!(
+            client.schema_by_id(9),
+            Err(KafkyError::SchemaRegistryError(_))
+        ));
+
+        assert_eq!(
+            split_wire_format(&wire_format(7, b"data")),
+            Ok((7, &b"data"[..]))
+        );
+        assert!(split_wire_format(b"data").is_err());
+        Ok(())
+    }
+}
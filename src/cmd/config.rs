@@ -1,7 +1,11 @@
-use crate::KafkyError;
-use clap::{App, ArgMatches, SubCommand};
-use log::debug;
-use std::io::{stdin, stdout, Write};
+use crate::cmd::config_manage::ConfigManageCmd;
+use crate::config::certificate::KafkyCertificateCheck;
+use crate::config::vault::KafkyVault;
+use crate::config::KafkySecretResolver;
+use crate::{KafkyConfig, KafkyError};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use log::{debug, error};
+use std::io::{stdin, stdout, IsTerminal, Write};
 use std::path::Path;
 use std::process::Command;
 
@@ -12,16 +16,286 @@ impl ConfigCmd {
         SubCommand::with_name("config")
             .about("kafky configuration")
             .subcommand(SubCommand::with_name("edit").about("edit the kafky configuration"))
+            .subcommand(Self::secret_command())
+            .subcommand(Self::check_certs_command())
+            .subcommands(ConfigManageCmd::commands())
+            .subcommand(
+                SubCommand::with_name("validate")
+                    .about("validate the kafky configuration, listing every problem found"),
+            )
+            .subcommand(
+                SubCommand::with_name("migrate")
+                    .about(
+                        "upgrade the configuration file to the current version, keeping a backup",
+                    )
+                    .arg(
+                        Arg::with_name("dry-run")
+                            .long("dry-run")
+                            .help("print the changes without writing them"),
+                    ),
+            )
     }
 
-    pub(super) fn exec(app_matches: &ArgMatches, config_file: &Path) -> Result<(), KafkyError> {
+    fn check_certs_command<'a>() -> App<'a, 'a> {
+        SubCommand::with_name("check-certs")
+            .about("inspect truststores, certificates and private keys, failing when a certificate expires soon")
+            .arg(
+                Arg::with_name("environment")
+                    .long("environment")
+                    .short("e")
+                    .multiple(true)
+                    .takes_value(true)
+                    .value_name("STRING")
+                    .help("environments to check, all when missing"),
+            )
+            .arg(
+                Arg::with_name("days")
+                    .long("days")
+                    .takes_value(true)
+                    .default_value("30")
+                    .help("fail if a certificate expires within the given number of days"),
+            )
+            .arg(
+                Arg::with_name("format")
+                    .long("output-format")
+                    .short("o")
+                    .takes_value(true)
+                    .default_value("table")
+                    .possible_values(&["table", "json"]),
+            )
+    }
+
+    fn secret_command<'a>() -> App<'a, 'a> {
+        let name_arg = Arg::with_name("name")
+            .required(true)
+            .value_name("NAME")
+            .help("secret name");
+        SubCommand::with_name("secret")
+            .about("manage the encrypted secrets vault, referenced in the credentials as `vault: NAME`")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("set")
+                    .about("add or replace a secret")
+                    .arg(name_arg.clone())
+                    .arg(
+                        Arg::with_name("value")
+                            .value_name("VALUE")
+                            .help("secret value, kept in the shell history: asked on the terminal or read from stdin when missing"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("get")
+                    .about("print a secret")
+                    .arg(name_arg.clone()),
+            )
+            .subcommand(SubCommand::with_name("list").about("list the secret names"))
+            .subcommand(
+                SubCommand::with_name("rm")
+                    .about("remove a secret")
+                    .arg(name_arg),
+            )
+    }
+
+    pub(super) fn exec(app_matches: &ArgMatches, config: &KafkyConfig) -> Result<(), KafkyError> {
+        let config_file = config.path();
         if app_matches.subcommand_matches("edit").is_some() {
             return Self::open_editor(config_file);
         }
+        if let Some(secret_args) = app_matches.subcommand_matches("secret") {
+            return Self::exec_secret(secret_args, config_file);
+        }
+        if let Some(result) = ConfigManageCmd::exec(app_matches.subcommand(), config_file) {
+            return result;
+        }
+        if let Some(migrate_args) = app_matches.subcommand_matches("migrate") {
+            return Self::exec_migrate(migrate_args, config_file);
+        }
+        if app_matches.subcommand_matches("validate").is_some() {
+            return Self::exec_validate(config);
+        }
+        if let Some(check_args) = app_matches.subcommand_matches("check-certs") {
+            return Self::exec_check_certs(check_args, config);
+        }
         Self::command().print_help().expect("error printing help");
         Ok(())
     }
 
+    fn exec_secret(secret_args: &ArgMatches, config_file: &Path) -> Result<(), KafkyError> {
+        let vault_path = KafkyConfig::vault_path_of(config_file);
+        // only `set` creates the vault
+        let mut vault = match secret_args.subcommand_name() {
+            Some("set") => KafkyVault::unlock_or_create(&vault_path)?,
+            _ => KafkyVault::unlock(&vault_path)?,
+        };
+        match secret_args.subcommand() {
+            ("set", Some(set_args)) => {
+                let name = set_args.value_of("name").unwrap();
+                let value = match set_args.value_of("value") {
+                    Some(value) => value.to_string(),
+                    None if stdin().is_terminal() => {
+                        rpassword::prompt_password(format!("{}: ", name))?
+                    }
+                    None => {
+                        let mut value = String::new();
+                        stdin().read_line(&mut value)?;
+                        value.trim_end_matches(['\r', '\n']).to_string()
+                    }
+                };
+                vault.set(name, &value);
+                vault.save()
+            }
+            ("get", Some(get_args)) => {
+                let name = get_args.value_of("name").unwrap();
+                let value = vault
+                    .get(name)
+                    .ok_or_else(|| KafkyError::VaultError(format!("secret {} not found", name)))?;
+                println!("{}", value);
+                Ok(())
+            }
+            ("list", Some(_)) => {
+                vault.names().iter().for_each(|name| println!("{}", name));
+                Ok(())
+            }
+            ("rm", Some(rm_args)) => {
+                let name = rm_args.value_of("name").unwrap();
+                vault
+                    .remove(name)
+                    .ok_or_else(|| KafkyError::VaultError(format!("secret {} not found", name)))?;
+                vault.save()
+            }
+            _ => Err(KafkyError::InvalidCommand()),
+        }
+    }
+
+    fn exec_migrate(migrate_args: &ArgMatches, config_file: &Path) -> Result<(), KafkyError> {
+        let migrations = if migrate_args.is_present("dry-run") {
+            KafkyConfig::migrations(config_file)?
+        } else {
+            KafkyConfig::migrate(config_file)?
+        };
+        if migrations.is_empty() {
+            println!("{} is up to date", config_file.display());
+        }
+        for migration in migrations {
+            if migrate_args.is_present("dry-run") {
+                print!("{}", migration.diff());
+            } else {
+                println!(
+                    "{} migrated from the version {} to {}, backup with the comments {}",
+                    migration.file.display(),
+                    migration.from,
+                    migration.to,
+                    migration.backup_path().display()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn exec_validate(config: &KafkyConfig) -> Result<(), KafkyError> {
+        let problems = config.validate();
+        if problems.is_empty() {
+            println!("{} is valid", config.path().display());
+            return Ok(());
+        }
+        problems.iter().for_each(|problem| println!("{}", problem));
+        Err(KafkyError::InvalidConfiguration(format!(
+            "{} problems found in {}",
+            problems.len(),
+            config.path().display()
+        )))
+    }
+
+    fn exec_check_certs(check_args: &ArgMatches, config: &KafkyConfig) -> Result<(), KafkyError> {
+        let days: i32 = check_args
+            .value_of("days")
+            .unwrap()
+            .parse()
+            .map_err(|_| KafkyError::ParseError("days must be a number".to_string()))?;
+        let environment_filter: Vec<&str> = check_args
+            .values_of("environment")
+            .unwrap_or_default()
+            .collect();
+        for environment in &environment_filter {
+            if config.get_environment(environment).is_none() {
+                return Err(KafkyError::EnvironmentNotFound(
+                    environment.to_string(),
+                    config.get_environment_names().join(","),
+                ));
+            }
+        }
+
+        let resolver = KafkySecretResolver::new(&config.vault_path());
+        let checks: Vec<KafkyCertificateCheck> = config
+            .environments
+            .iter()
+            .filter(|e| {
+                environment_filter.is_empty() || environment_filter.contains(&e.name.as_str())
+            })
+            .flat_map(|e| e.check_certificates(&resolver))
+            .collect();
+
+        match check_args.value_of("format").unwrap() {
+            "json" => println!("{}", serde_json::to_string(&checks)?),
+            "table" => Self::print_certificates_table(&checks),
+            _ => error!("invalid format"),
+        }
+
+        let failed = checks.iter().filter(|c| c.is_failed(days)).count();
+        if failed > 0 {
+            return Err(KafkyError::CertificateCheckFailed(format!(
+                "{} of {} checks failed (unreadable, key mismatch or expiring within {} days)",
+                failed,
+                checks.len(),
+                days
+            )));
+        }
+        Ok(())
+    }
+
+    fn print_certificates_table(checks: &[KafkyCertificateCheck]) {
+        let mut result_table = tabwriter::TabWriter::new(vec![]);
+        result_table
+            .write_all(b"ENVIRONMENT\tCREDENTIAL\tSUBJECT\tISSUER\tSANS\tNOT AFTER\tDAYS LEFT\tKEY MATCH\n")
+            .expect("error creating table header");
+        for check in checks {
+            let credential = check.credential.as_deref().unwrap_or("(truststore)");
+            if let Some(e) = &check.error {
+                result_table
+                    .write_all(
+                        format!("{}\t{}\tERROR: {}\n", check.environment, credential, e).as_ref(),
+                    )
+                    .expect("error writing table data");
+            }
+            for (i, certificate) in check.certificates.iter().enumerate() {
+                let key_match = match check.key_match {
+                    Some(key_match) if i == 0 => key_match.to_string(),
+                    _ => "-".to_string(),
+                };
+                result_table
+                    .write_all(
+                        format!(
+                            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                            check.environment,
+                            credential,
+                            certificate.subject,
+                            certificate.issuer,
+                            certificate.sans.join(","),
+                            certificate.not_after,
+                            certificate.days_left,
+                            key_match
+                        )
+                        .as_ref(),
+                    )
+                    .expect("error writing table data");
+            }
+        }
+        result_table.flush().expect("error printing table");
+        stdout()
+            .write_all(&result_table.into_inner().unwrap())
+            .expect("error printing table");
+    }
+
     pub fn open_editor(config_file: &Path) -> Result<(), KafkyError> {
         print!("Which editor do you prefer ot open it (vim/nano/..)? ");
         stdout().flush().unwrap();
@@ -32,7 +306,7 @@ impl ConfigCmd {
         open_cmd.push(' ');
         open_cmd.push_str(config_file.as_os_str().to_str().unwrap());
 
-        let sh_path = which::which("sh").expect(&*"bash (sh) not found".to_string());
+        let sh_path = which::which("sh").expect("bash (sh) not found");
 
         let mut editor_cmd = Command::new(sh_path);
         let final_editor_cmd = editor_cmd.arg("-c").arg(open_cmd);
@@ -0,0 +1,702 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{stdout, Write};
+use std::path::Path;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use log::error;
+use serde::Serialize;
+
+use crate::config::properties::{parse_properties, parse_property, write_properties};
+use crate::config::{
+    KafkyCredential, KafkyCredentialKind, KafkyEnvironment, KafkyOAuthBearerCredential, KafkyPEM,
+    KafkyPlainCredential, KafkyPrivateKey, KafkySSLCredential, KafkyScramCredential,
+    KafkyScramMechanism, KafkySecret, KafkySecretReference, KafkySecretResolver,
+    KafkySecurityProtocol,
+};
+use crate::{KafkyConfig, KafkyError};
+
+const CREDENTIAL_TYPES: &[&str] = &["plain", "scram", "ssl", "oauthbearer"];
+const SECURITY_PROTOCOLS: &[&str] = &["PLAINTEXT", "SSL", "SASL_PLAINTEXT", "SASL_SSL"];
+
+#[derive(Serialize)]
+struct EnvironmentRow<'a> {
+    name: &'a str,
+    brokers: &'a [String],
+    #[serde(rename = "securityProtocol", skip_serializing_if = "Option::is_none")]
+    security_protocol: Option<String>,
+    credentials: Vec<CredentialRow<'a>>,
+}
+
+#[derive(Serialize)]
+struct CredentialRow<'a> {
+    name: &'a str,
+    #[serde(rename = "type")]
+    kind: &'a str,
+}
+
+/// config subcommands editing the configuration file without an editor
+pub struct ConfigManageCmd {}
+
+impl ConfigManageCmd {
+    pub(super) fn commands<'a>() -> Vec<App<'a, 'a>> {
+        vec![
+            SubCommand::with_name("list")
+                .about("list environments and credentials")
+                .arg(
+                    Arg::with_name("format")
+                        .long("output-format")
+                        .short("o")
+                        .takes_value(true)
+                        .default_value("table")
+                        .possible_values(&["table", "json"]),
+                ),
+            SubCommand::with_name("add-environment")
+                .about("add an environment, credentials are added with add-credential")
+                .arg(Self::name_arg("environment name"))
+                .arg(
+                    Arg::with_name("brokers")
+                        .long("broker")
+                        .short("b")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .required(true)
+                        .value_name("HOST:PORT"),
+                )
+                .arg(
+                    Arg::with_name("truststore")
+                        .long("truststore")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .help("CA certificates PEM file"),
+                )
+                .arg(
+                    Arg::with_name("security-protocol")
+                        .long("security-protocol")
+                        .takes_value(true)
+                        .possible_values(SECURITY_PROTOCOLS),
+                )
+                .arg(
+                    Arg::with_name("read-only")
+                        .long("read-only")
+                        .help("block the commands writing to the cluster"),
+                )
+                .arg(
+                    Arg::with_name("protected")
+                        .long("protected")
+                        .help("confirm destructive commands by typing the environment name"),
+                )
+                .arg(Self::property_arg()),
+            SubCommand::with_name("remove-environment")
+                .about("remove an environment and its credentials")
+                .arg(Self::name_arg("environment name")),
+            SubCommand::with_name("set-brokers")
+                .about("replace the brokers of an environment")
+                .arg(Self::name_arg("environment name"))
+                .arg(
+                    Arg::with_name("brokers")
+                        .required(true)
+                        .multiple(true)
+                        .value_name("HOST:PORT"),
+                ),
+            Self::add_credential_command(),
+            SubCommand::with_name("remove-credential")
+                .about("remove a credential from an environment")
+                .arg(Self::environment_arg())
+                .arg(Self::name_arg("credential name")),
+            SubCommand::with_name("use")
+                .about("set the default environment and optionally its default credential")
+                .arg(
+                    Arg::with_name("environment")
+                        .required(true)
+                        .value_name("ENVIRONMENT"),
+                )
+                .arg(Arg::with_name("credential").value_name("CREDENTIAL")),
+            SubCommand::with_name("import")
+                .about("add an environment from a Kafka client .properties file")
+                .arg(
+                    Arg::with_name("properties")
+                        .long("properties")
+                        .short("p")
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .long("name")
+                        .short("n")
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("NAME")
+                        .help("environment name"),
+                )
+                .arg(
+                    Arg::with_name("credential")
+                        .long("credential")
+                        .short("c")
+                        .takes_value(true)
+                        .default_value("default")
+                        .value_name("NAME")
+                        .help("credential name"),
+                ),
+            SubCommand::with_name("export")
+                .about("write the Kafka client .properties of an environment, secrets included")
+                .arg(Self::environment_arg())
+                .arg(
+                    Arg::with_name("credential")
+                        .long("credential")
+                        .short("c")
+                        .takes_value(true)
+                        .value_name("NAME")
+                        .help("credential name, needed when the environment has more than one"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("properties file to write, standard output when missing"),
+                ),
+        ]
+    }
+
+    fn name_arg<'a>(help: &'a str) -> Arg<'a, 'a> {
+        Arg::with_name("name")
+            .required(true)
+            .value_name("NAME")
+            .help(help)
+    }
+
+    fn property_arg<'a>() -> Arg<'a, 'a> {
+        Arg::with_name("property")
+            .short("X")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("KEY=VALUE")
+            .help("librdkafka property")
+    }
+
+    fn environment_arg<'a>() -> Arg<'a, 'a> {
+        Arg::with_name("environment")
+            .long("environment")
+            .short("e")
+            .takes_value(true)
+            .required(true)
+            .value_name("STRING")
+    }
+
+    fn add_credential_command<'a>() -> App<'a, 'a> {
+        SubCommand::with_name("add-credential")
+            .about("add a credential to an environment")
+            .setting(AppSettings::DeriveDisplayOrder)
+            .arg(Self::environment_arg())
+            .arg(Self::name_arg("credential name"))
+            .arg(
+                Arg::with_name("type")
+                    .long("type")
+                    .short("t")
+                    .takes_value(true)
+                    .required(true)
+                    .possible_values(CREDENTIAL_TYPES),
+            )
+            .arg(
+                Arg::with_name("username")
+                    .long("username")
+                    .takes_value(true)
+                    .required_ifs(&[("type", "plain"), ("type", "scram")]),
+            )
+            .arg(
+                Arg::with_name("password")
+                    .long("password")
+                    .visible_alias("client-secret")
+                    .takes_value(true)
+                    .required_ifs(&[
+                        ("type", "plain"),
+                        ("type", "scram"),
+                        ("type", "oauthbearer"),
+                    ])
+                    .help("password, private key password for ssl or client secret for oauthbearer"),
+            )
+            .arg(
+                Arg::with_name("password-source")
+                    .long("password-source")
+                    .takes_value(true)
+                    .default_value("value")
+                    .possible_values(&["value", "env", "file", "command", "vault"])
+                    .help("how the password is read: the value itself, an env variable, a file, a command or a vault entry"),
+            )
+            .arg(
+                Arg::with_name("mechanism")
+                    .long("mechanism")
+                    .takes_value(true)
+                    .possible_values(&["SCRAM-SHA-256", "SCRAM-SHA-512"])
+                    .help("scram mechanism, default SCRAM-SHA-512"),
+            )
+            .arg(
+                Arg::with_name("certificate")
+                    .long("certificate")
+                    .takes_value(true)
+                    .value_name("PATH")
+                    .required_if("type", "ssl")
+                    .help("client certificate PEM file"),
+            )
+            .arg(
+                Arg::with_name("private-key")
+                    .long("private-key")
+                    .takes_value(true)
+                    .value_name("PATH")
+                    .required_if("type", "ssl")
+                    .help("private key PEM file"),
+            )
+            .arg(
+                Arg::with_name("client-id")
+                    .long("client-id")
+                    .takes_value(true)
+                    .required_if("type", "oauthbearer"),
+            )
+            .arg(
+                Arg::with_name("token-endpoint-url")
+                    .long("token-endpoint-url")
+                    .takes_value(true)
+                    .required_if("type", "oauthbearer"),
+            )
+            .arg(Arg::with_name("scope").long("scope").takes_value(true))
+            .arg(Self::property_arg())
+    }
+
+    /// executes the subcommand if it's a management one
+    pub(super) fn exec(
+        sub_command: (&str, Option<&ArgMatches>),
+        config_file: &Path,
+    ) -> Option<Result<(), KafkyError>> {
+        let args = sub_command.1?;
+        let result = match sub_command.0 {
+            "list" => KafkyConfig::load(config_file).map(|config| Self::list(&config, args)),
+            "add-environment" => Self::update(config_file, |config| {
+                config.add_environment(KafkyEnvironment {
+                    name: args.value_of("name").unwrap().to_string(),
+                    brokers: Self::brokers(args),
+                    credentials: vec![],
+                    truststore: args
+                        .value_of("truststore")
+                        .map(|path| KafkyPEM::Path(path.to_string())),
+                    security_protocol: args
+                        .value_of("security-protocol")
+                        .map(Self::security_protocol),
+                    properties: Self::properties(args)?,
+                    default_credential: None,
+                    read_only: args.is_present("read-only"),
+                    protected: args.is_present("protected"),
+                    schema_registry: None,
+                })
+            }),
+            "remove-environment" => Self::update(config_file, |config| {
+                let name = args.value_of("name").unwrap();
+                config.remove_environment(name)?;
+                if config.default_environment.as_deref() == Some(name) {
+                    config.default_environment = None;
+                }
+                Ok(())
+            }),
+            "use" => Self::use_context(config_file, args),
+            "set-brokers" => Self::update(config_file, |config| {
+                config
+                    .get_environment_mut(args.value_of("name").unwrap())?
+                    .brokers = Self::brokers(args);
+                Ok(())
+            }),
+            "add-credential" => Self::update(config_file, |config| {
+                config
+                    .get_environment_mut(args.value_of("environment").unwrap())?
+                    .add_credential(KafkyCredential {
+                        name: args.value_of("name").unwrap().to_string(),
+                        credential: Self::credential_kind(args),
+                        properties: Self::properties(args)?,
+                    })
+            }),
+            "remove-credential" => Self::update(config_file, |config| {
+                config
+                    .get_environment_mut(args.value_of("environment").unwrap())?
+                    .remove_credential(args.value_of("name").unwrap())
+                    .map(|_| ())
+            }),
+            "import" => Self::update(config_file, |config| {
+                let properties_file = args.value_of("properties").unwrap();
+                let properties = parse_properties(&fs::read_to_string(properties_file)?);
+                let environment = KafkyEnvironment::from_properties(
+                    args.value_of("name").unwrap(),
+                    args.value_of("credential").unwrap(),
+                    &properties,
+                )
+                .map_err(|e| KafkyError::PropertiesError(properties_file.to_string(), e))?;
+                config.add_environment(environment)
+            }),
+            "export" => {
+                KafkyConfig::load(config_file).and_then(|config| Self::export(&config, args))
+            }
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    /// loads the configuration file, applies the change and saves it
+    fn update<F>(config_file: &Path, change: F) -> Result<(), KafkyError>
+    where
+        F: FnOnce(&mut KafkyConfig) -> Result<(), KafkyError>,
+    {
+        // included files are never modified
+        let mut config = KafkyConfig::load_file(config_file)?;
+        change(&mut config)?;
+        config.save()?;
+        for problem in KafkyConfig::load(config_file)?.validate() {
+            eprintln!("warning: {}", problem);
+        }
+        Ok(())
+    }
+
+    fn properties(args: &ArgMatches) -> Result<BTreeMap<String, String>, KafkyError> {
+        args.values_of("property")
+            .unwrap_or_default()
+            .map(parse_property)
+            .collect()
+    }
+
+    fn brokers(args: &ArgMatches) -> Vec<String> {
+        args.values_of("brokers")
+            .unwrap_or_default()
+            .map(|b| b.to_string())
+            .collect()
+    }
+
+    fn security_protocol(protocol: &str) -> KafkySecurityProtocol {
+        match protocol {
+            "PLAINTEXT" => KafkySecurityProtocol::Plaintext,
+            "SSL" => KafkySecurityProtocol::Ssl,
+            "SASL_PLAINTEXT" => KafkySecurityProtocol::SaslPlaintext,
+            _ => KafkySecurityProtocol::SaslSsl,
+        }
+    }
+
+    fn password(args: &ArgMatches) -> Option<KafkySecret> {
+        let password = args.value_of("password")?.to_string();
+        Some(match args.value_of("password-source").unwrap() {
+            "env" => KafkySecret::Reference(KafkySecretReference::Env(password)),
+            "file" => KafkySecret::Reference(KafkySecretReference::File(password)),
+            "command" => KafkySecret::Reference(KafkySecretReference::Command(password)),
+            "vault" => KafkySecret::Reference(KafkySecretReference::Vault(password)),
+            _ => KafkySecret::Value(password),
+        })
+    }
+
+    fn credential_kind(args: &ArgMatches) -> KafkyCredentialKind {
+        let value = |name| args.value_of(name).unwrap().to_string();
+        match args.value_of("type").unwrap() {
+            "plain" => KafkyCredentialKind::Plain(KafkyPlainCredential {
+                username: value("username"),
+                password: Self::password(args).unwrap(),
+            }),
+            "scram" => KafkyCredentialKind::Scram(KafkyScramCredential {
+                mechanism: match args.value_of("mechanism") {
+                    Some("SCRAM-SHA-256") => KafkyScramMechanism::ScramSha256,
+                    _ => KafkyScramMechanism::ScramSha512,
+                },
+                username: value("username"),
+                password: Self::password(args).unwrap(),
+            }),
+            "ssl" => KafkyCredentialKind::Ssl(KafkySSLCredential {
+                certificate: KafkyPEM::Path(value("certificate")),
+                private_key: Some(KafkyPrivateKey {
+                    key: KafkyPEM::Path(value("private-key")),
+                    password: Self::password(args),
+                }),
+            }),
+            _ => KafkyCredentialKind::OAuthBearer(KafkyOAuthBearerCredential {
+                client_id: value("client-id"),
+                client_secret: Self::password(args).unwrap(),
+                token_endpoint_url: value("token-endpoint-url"),
+                scope: args.value_of("scope").map(|s| s.to_string()),
+                extensions: BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// the environment and credential must exist, even if defined in an included file
+    fn use_context(config_file: &Path, args: &ArgMatches) -> Result<(), KafkyError> {
+        let environment = args.value_of("environment").unwrap();
+        let credential = args.value_of("credential");
+        let merged_config = KafkyConfig::load(config_file)?;
+        let merged_environment = merged_config.get_environment(environment).ok_or_else(|| {
+            KafkyError::EnvironmentNotFound(
+                environment.to_string(),
+                merged_config.get_environment_names().join(","),
+            )
+        })?;
+        if let Some(credential) = credential {
+            if merged_environment.get_credential(credential).is_none() {
+                return Err(KafkyError::CredentialNotFound(
+                    credential.to_string(),
+                    environment.to_string(),
+                    merged_environment.get_credential_names().join(","),
+                ));
+            }
+        }
+        Self::update(config_file, |config| {
+            config.default_environment = Some(environment.to_string());
+            if let Some(credential) = credential {
+                if config.get_environment(environment).is_none() {
+                    // the environment comes from an included file, the main file only sets its default credential
+                    config.environments.push(KafkyEnvironment {
+                        name: environment.to_string(),
+                        brokers: vec![],
+                        credentials: vec![],
+                        truststore: None,
+                        security_protocol: None,
+                        properties: BTreeMap::new(),
+                        default_credential: None,
+                        read_only: false,
+                        protected: false,
+                        schema_registry: None,
+                    });
+                }
+                config.get_environment_mut(environment)?.default_credential =
+                    Some(credential.to_string());
+            }
+            Ok(())
+        })
+    }
+
+    fn export(config: &KafkyConfig, args: &ArgMatches) -> Result<(), KafkyError> {
+        let environment_name = args.value_of("environment").unwrap();
+        let environment = config.get_environment(environment_name).ok_or_else(|| {
+            KafkyError::EnvironmentNotFound(
+                environment_name.to_string(),
+                config.get_environment_names().join(","),
+            )
+        })?;
+        let credential = match args.value_of("credential") {
+            Some(name) => environment.get_credential(name).ok_or_else(|| {
+                KafkyError::CredentialNotFound(
+                    name.to_string(),
+                    environment_name.to_string(),
+                    environment.get_credential_names().join(","),
+                )
+            })?,
+            None if environment.credentials.len() == 1 => &environment.credentials[0],
+            None => {
+                return Err(KafkyError::NoCredentialSpecified(
+                    environment.get_credential_names().join(","),
+                ))
+            }
+        };
+        let resolver = KafkySecretResolver::new(&config.vault_path());
+        let properties = environment
+            .to_properties(credential, &resolver)
+            .map_err(|e| KafkyError::PropertiesError(environment_name.to_string(), e))?;
+        let content = write_properties(&properties);
+        match args.value_of("output") {
+            None => print!("{}", content),
+            Some(output) => {
+                fs::write(output, content)?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(output, fs::Permissions::from_mode(0o600))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn list(config: &KafkyConfig, args: &ArgMatches) {
+        let rows: Vec<EnvironmentRow> = config
+            .environments
+            .iter()
+            .map(|e| EnvironmentRow {
+                name: &e.name,
+                brokers: &e.brokers,
+                security_protocol: e.security_protocol.map(|p| p.to_string()),
+                credentials: e
+                    .credentials
+                    .iter()
+                    .map(|c| CredentialRow {
+                        name: &c.name,
+                        kind: c.credential.kind_name(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        match args.value_of("format").unwrap() {
+            "json" => println!("{}", serde_json::to_string(&rows).expect("invalid json")),
+            "table" => Self::print_table(&rows),
+            _ => error!("invalid format"),
+        }
+    }
+
+    fn print_table(rows: &[EnvironmentRow]) {
+        let mut result_table = tabwriter::TabWriter::new(vec![]);
+        result_table
+            .write_all(b"ENVIRONMENT\tBROKERS\tCREDENTIALS\n")
+            .expect("error creating table header");
+        for row in rows {
+            let credentials: Vec<String> = row
+                .credentials
+                .iter()
+                .map(|c| format!("{}({})", c.name, c.kind))
+                .collect();
+            result_table
+                .write_all(
+                    format!(
+                        "{}\t{}\t{}\n",
+                        row.name,
+                        row.brokers.join(","),
+                        credentials.join(",")
+                    )
+                    .as_ref(),
+                )
+                .expect("error writing table data");
+        }
+        result_table.flush().expect("error printing table");
+        stdout()
+            .write_all(&result_table.into_inner().unwrap())
+            .expect("error printing table");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    fn run(config_file: &Path, args: &[&str]) -> Result<(), KafkyError> {
+        let app = App::new("config").subcommands(ConfigManageCmd::commands());
+        let matches = app
+            .get_matches_from_safe([&["config"], args].concat())
+            .unwrap();
+        ConfigManageCmd::exec(matches.subcommand(), config_file).unwrap()
+    }
+
+    #[test]
+    fn manage_environments_test() -> Result<(), KafkyError> {
+        let config_dir = tempfile::tempdir()?;
+        let config_file = config_dir.path().join("config.yml");
+        std::fs::write(&config_file, "environments: []\n")?;
+
+        run(
+            &config_file,
+            &["add-environment", "dev", "-b", "localhost:9092"],
+        )?;
+        run(
+            &config_file,
+            &["set-brokers", "dev", "kafka-1:9092", "kafka-2:9092"],
+        )?;
+        run(
+            &config_file,
+            &[
+                "add-credential",
+                "-e",
+                "dev",
+                "admin",
+                "-t",
+                "scram",
+                "--username",
+                "admin",
+                "--password",
+                "KAFKA_PASSWORD",
+                "--password-source",
+                "env",
+            ],
+        )?;
+        run(
+            &config_file,
+            &[
+                "add-credential",
+                "-e",
+                "dev",
+                "user",
+                "-t",
+                "plain",
+                "--username",
+                "user",
+                "--password",
+                "pwd",
+            ],
+        )?;
+        assert!(matches!(
+            run(
+                &config_file,
+                &["add-environment", "dev", "-b", "localhost:9092"]
+            ),
+            Err(KafkyError::EnvironmentAlreadyExists(_))
+        ));
+        run(&config_file, &["remove-credential", "-e", "dev", "user"])?;
+
+        assert_eq!(
+            std::fs::read_to_string(&config_file)?,
+            indoc! {"
+                ---
+                version: 2
+                environments:
+                  - name: dev
+                    brokers:
+                      - \"kafka-1:9092\"
+                      - \"kafka-2:9092\"
+                    credentials:
+                      - name: admin
+                        scram:
+                          mechanism: SCRAM-SHA-512
+                          username: admin
+                          password:
+                            env: KAFKA_PASSWORD
+            "}
+        );
+
+        run(&config_file, &["remove-environment", "dev"])?;
+        assert!(KafkyConfig::load(&config_file)?.environments.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn use_test() -> Result<(), KafkyError> {
+        let config_dir = tempfile::tempdir()?;
+        let config_file = config_dir.path().join("config.yml");
+        std::fs::write(&config_file, "include:\n  - team.yml\nenvironments: []\n")?;
+        std::fs::write(
+            config_dir.path().join("team.yml"),
+            indoc! {"
+                environments:
+                  - name: prod
+                    brokers:
+                      - kafka-1:9093
+                    credentials:
+                      - name: admin
+                        plain:
+                          username: admin
+                          password: admin
+                      - name: reader
+                        plain:
+                          username: reader
+                          password: reader
+            "},
+        )?;
+
+        assert!(matches!(
+            run(&config_file, &["use", "prod", "unknown"]),
+            Err(KafkyError::CredentialNotFound(_, _, _))
+        ));
+        run(&config_file, &["use", "prod", "reader"])?;
+        let config = KafkyConfig::load(&config_file)?;
+        assert_eq!(config.default_environment.as_deref(), Some("prod"));
+        let prod = config.get_environment("prod").unwrap();
+        assert_eq!(prod.default_credential.as_deref(), Some("reader"));
+        assert_eq!(prod.get_credential_names(), vec!["admin", "reader"]);
+        assert!(config.validate().is_empty());
+
+        run(&config_file, &["remove-environment", "prod"])?;
+        assert_eq!(KafkyConfig::load(&config_file)?.default_environment, None);
+        Ok(())
+    }
+}
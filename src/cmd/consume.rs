@@ -1,19 +1,66 @@
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+use std::num::NonZeroU64;
 use std::str::FromStr;
 
+use chrono::{DateTime, Utc};
 use clap::{App, Arg, ArgMatches, SubCommand};
 use gethostname::gethostname;
 use log::error;
+use serde::Serialize;
+use serde_json::Value;
+use strum::VariantNames;
+use tokio::sync::oneshot::Receiver;
 
-use crate::client::consumer::{KafkyConsumeProperties, KafkyConsumerOffset};
+use crate::client::consumer::{
+    serialize_dt, KafkyConsumeProperties, KafkyConsumerOffset, KafkyConsumerPosition, KafkyHeader,
+};
+use crate::client::encoding::{KafkyEncodedData, KafkyEncoding};
+use crate::client::format::{KafkyDecoder, KafkyFormat};
 use crate::client::kafky_client::KafkyClient;
+use crate::cmd::filter::{KafkyFilter, KafkyFilterStats};
+use crate::cmd::format;
 use crate::errors::KafkyError;
 
 pub struct ConsumeCmd {}
 
+/// consumed message with the key and the payload rendered in the requested encodings
+#[derive(Serialize)]
+pub(super) struct KafkyRenderedMessage<'a> {
+    pub(super) key: Option<KafkyEncodedData>,
+    pub(super) topic: &'a str,
+    pub(super) payload: KafkyEncodedData,
+    pub(super) partition: i32,
+    pub(super) offset: i64,
+    #[serde(
+        serialize_with = "serialize_dt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(super) timestamp: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(super) headers: &'a Vec<KafkyHeader>,
+    /// text payload parsed once for all the JSON path filters
+    #[serde(skip)]
+    pub(super) parsed_payload: OnceCell<Option<Value>>,
+}
+
+impl KafkyRenderedMessage<'_> {
+    /// the payload as JSON, `None` if it's not
+    pub(super) fn json_payload(&self) -> Option<&Value> {
+        match &self.payload {
+            KafkyEncodedData::Json(json) => Some(json),
+            text => self
+                .parsed_payload
+                .get_or_init(|| serde_json::from_str(&text.to_string()).ok())
+                .as_ref(),
+        }
+    }
+}
+
 impl ConsumeCmd {
     pub fn command<'a>() -> App<'a, 'a> {
         let hostname = Box::leak(Box::new(gethostname()));
-        SubCommand::with_name("consume")
+        let consume = SubCommand::with_name("consume")
             .about("Consume messages from topics")
             .arg(
                 Arg::with_name("topic")
@@ -51,6 +98,39 @@ impl ConsumeCmd {
                     .long("timestamp")
                     .help("print timestamp message (works only with text format)"),
             )
+            .arg(
+                Arg::with_name("key-encoding")
+                    .long("key-encoding")
+                    .possible_values(KafkyEncoding::VARIANTS)
+                    .default_value("utf8")
+                    .help("rendering of the keys, auto is utf8 when valid and base64 otherwise"),
+            )
+            .arg(
+                Arg::with_name("value-encoding")
+                    .long("value-encoding")
+                    .possible_values(KafkyEncoding::VARIANTS)
+                    .default_value("utf8")
+                    .help("rendering of the values, auto is utf8 when valid and base64 otherwise"),
+            )
+            .arg(
+                Arg::with_name("key-format")
+                    .long("key-format")
+                    .possible_values(KafkyFormat::VARIANTS)
+                    .default_value("raw")
+                    .help("format of the keys, avro, protobuf and json are rendered as JSON"),
+            )
+            .arg(
+                Arg::with_name("value-format")
+                    .long("value-format")
+                    .possible_values(KafkyFormat::VARIANTS)
+                    .default_value("raw")
+                    .help("format of the values, avro, protobuf and json are rendered as JSON"),
+            )
+            .arg(
+                Arg::with_name("headers")
+                    .long("headers")
+                    .help("print the headers as @k1=v1,k2=v2 (works only with text format)"),
+            )
             .arg(
                 Arg::with_name("earliest")
                     .long("earliest")
@@ -63,64 +143,336 @@ impl ConsumeCmd {
                     .help("read from the latest offset (default)")
                     .group("offset"),
             )
+            .arg(
+                Arg::with_name("from-time")
+                    .long("from-time")
+                    .value_name("RFC3339")
+                    .help("read from the first message at or after the time, e.g. 2026-10-01T10:00:00Z")
+                    .group("offset"),
+            )
+            .arg(
+                Arg::with_name("since")
+                    .long("since")
+                    .value_name("DURATION")
+                    .help("read from the messages of the last duration, e.g. 15m, 2h, 1day")
+                    .group("offset"),
+            )
+            .arg(
+                Arg::with_name("last")
+                    .long("last")
+                    .value_name("N")
+                    .help("read the last N messages of each partition")
+                    .group("offset"),
+            )
+            .arg(
+                Arg::with_name("exit")
+                    .long("exit")
+                    .requires("last")
+                    .help("stop once the messages that were there at the start are read"),
+            )
+            .arg(
+                Arg::with_name("max-messages")
+                    .long("max-messages")
+                    .short("n")
+                    .value_name("N")
+                    .help("stop after N printed messages, N matching ones with filters"),
+            )
+            .arg(
+                Arg::with_name("until")
+                    .long("until")
+                    .value_name("RFC3339")
+                    .help("stop at the first message after the time, or when the time has come"),
+            )
+            .arg(
+                Arg::with_name("exit-on-eof")
+                    .long("exit-on-eof")
+                    .help("stop once every partition is read to the end"),
+            )
+            .arg(
+                Arg::with_name("partition")
+                    .long("partition")
+                    .short("p")
+                    .multiple(true)
+                    .number_of_values(1)
+                    .value_name("PARTITION")
+                    .help("read only the partition, without joining the consumer group"),
+            )
+            .arg(
+                Arg::with_name("partition-offset")
+                    .long("offset")
+                    .multiple(true)
+                    .number_of_values(1)
+                    .value_name("OFFSET|PARTITION:OFFSET")
+                    .help("read from the offset, of every partition or of the given one")
+                    .group("offset"),
+            )
+            .arg(
+                Arg::with_name("filter")
+                    .long("filter")
+                    .multiple(true)
+                    .number_of_values(1)
+                    .value_name("EXPRESSION")
+                    .help("print only the matching messages, e.g. '$.order.status == \"FAILED\"', all the filters must match"),
+            );
+        format::protobuf_args(consume)
     }
 
+    /// `stop_rx` ends the consumption on ctrl+c
     pub async fn exec<'a>(
         app_matches: &'a ArgMatches<'a>,
         kafky_client: &'a KafkyClient<'a>,
+        stop_rx: Receiver<bool>,
     ) -> Result<(), KafkyError> {
         let format: &str = app_matches.value_of("format").unwrap();
         let topics: Vec<&str> = app_matches.values_of("topic").unwrap().collect();
+        let key_format = format::extract_format(app_matches, "key-format")?;
+        let value_format = format::extract_format(app_matches, "value-format")?;
+        let registry = if key_format == KafkyFormat::Avro || value_format == KafkyFormat::Avro {
+            Some(kafky_client.schema_registry()?)
+        } else {
+            None
+        };
+        let (key_message, value_message) =
+            format::extract_messages(app_matches, key_format, value_format)?;
+        let key_decoder = KafkyDecoder::new(
+            key_format,
+            Self::extract_encoding(app_matches, "key-encoding")?,
+            registry.as_ref(),
+        )
+        .with_message(key_message);
+        let value_decoder = KafkyDecoder::new(
+            value_format,
+            Self::extract_encoding(app_matches, "value-encoding")?,
+            registry.as_ref(),
+        )
+        .with_message(value_message);
+        let filters = app_matches
+            .values_of("filter")
+            .unwrap_or_default()
+            .map(KafkyFilter::parse)
+            .collect::<Result<Vec<KafkyFilter>, KafkyError>>()?;
+        // only the printed messages count: tombstones, undecodable and filtered out ones don't
+        let mut stats = KafkyFilterStats::new(Self::extract_max_messages(app_matches)?);
         kafky_client
-            .consume::<str, str, _>(
+            .consume::<[u8], [u8], _>(
                 &KafkyConsumeProperties {
                     topics: &topics,
                     consumer_group: app_matches.value_of("consumer-group").unwrap(),
                     offset: Self::extract_offset_from_arg(app_matches)?,
                     auto_commit: app_matches.is_present("autocommit"),
+                    position: Self::extract_position_from_arg(app_matches)?,
+                    partitions: Self::extract_partitions_from_arg(app_matches)?,
+                    stop_at_end: app_matches.is_present("exit"),
+                    exit_on_eof: app_matches.is_present("exit-on-eof"),
+                    until: app_matches
+                        .value_of("until")
+                        .map(|until| Self::parse_time(until).map(|t| t.timestamp_millis()))
+                        .transpose()?,
                 },
-                None,
+                Some(stop_rx),
                 |msg_result| match msg_result {
-                    Ok(msg) => match format {
-                        "json" => {
-                            println!("{}", serde_json::to_string(&msg).unwrap());
-                            true
+                    Ok(msg) => {
+                        stats.scanned += 1;
+                        let decoded = msg
+                            .key()
+                            .map(|key| key_decoder.decode(key))
+                            .transpose()
+                            .map_err(|e| KafkyError::DecodeError("key".to_string(), e))
+                            .and_then(|key| {
+                                value_decoder
+                                    .decode(msg.payload())
+                                    .map(|payload| (key, payload))
+                                    .map_err(|e| KafkyError::DecodeError("value".to_string(), e))
+                            });
+                        let (key, payload) = match decoded {
+                            Ok(decoded) => decoded,
+                            Err(e) => {
+                                // the message is skipped, the next ones may be decodable
+                                error!(
+                                    "{} (partition {} offset {})",
+                                    e,
+                                    msg.partition(),
+                                    msg.offset()
+                                );
+                                return true;
+                            }
+                        };
+                        let rendered = KafkyRenderedMessage {
+                            key,
+                            topic: msg.topic(),
+                            payload,
+                            partition: msg.partition(),
+                            offset: msg.offset(),
+                            timestamp: msg.timestamp(),
+                            headers: msg.headers(),
+                            parsed_payload: OnceCell::new(),
+                        };
+                        if !filters.iter().all(|filter| filter.matches(&rendered)) {
+                            return true;
                         }
-                        "text" => {
-                            let mut row = String::new();
-                            if topics.len() > 1 {
-                                row.push_str(msg.topic());
-                                row.push_str(" -> ");
+                        match format {
+                            "json" => {
+                                println!("{}", serde_json::to_string(&rendered).unwrap());
                             }
-                            if app_matches.is_present("timestamp") {
-                                row.push('[');
-                                row.push_str(
-                                    &msg.timestamp()
-                                        .map(|t| t.to_rfc3339())
-                                        .unwrap_or_else(|| String::from("NO-TS")),
-                                );
-                                row.push_str("] ");
+                            "text" => {
+                                println!("{}", Self::text_row(app_matches, &topics, &rendered));
                             }
-                            if app_matches.is_present("key-separator") {
-                                row.push_str(msg.key().unwrap_or("null"));
-                                row.push_str(app_matches.value_of("key-separator").unwrap());
+                            _ => {
+                                error!("invalid format");
+                                return false;
                             }
-                            row.push_str(msg.payload());
-                            println!("{}", row);
-                            true
                         }
-                        _ => {
-                            error!("invalid format");
-                            false
-                        }
-                    },
+                        stats.matched()
+                    }
                     Err(err) => {
                         error!("error: {:?}", err);
                         false
                     }
                 },
             )
-            .await
+            .await?;
+        if !filters.is_empty() {
+            stats.print();
+        }
+        Ok(())
+    }
+
+    fn extract_max_messages(app_matches: &ArgMatches<'_>) -> Result<Option<u64>, KafkyError> {
+        app_matches
+            .value_of("max-messages")
+            .map(|max| {
+                max.parse::<NonZeroU64>()
+                    .map(NonZeroU64::get)
+                    .map_err(|e| KafkyError::ParseError(format!("max-messages {}: {}", max, e)))
+            })
+            .transpose()
+    }
+
+    fn extract_encoding(
+        app_matches: &ArgMatches<'_>,
+        name: &str,
+    ) -> Result<KafkyEncoding, KafkyError> {
+        let encoding = app_matches.value_of(name).unwrap();
+        KafkyEncoding::from_str(encoding)
+            .map_err(|_| KafkyError::ParseError(format!("invalid {} {}", name, encoding)))
+    }
+
+    fn text_row(
+        app_matches: &ArgMatches<'_>,
+        topics: &[&str],
+        msg: &KafkyRenderedMessage,
+    ) -> String {
+        let mut row = String::new();
+        if topics.len() > 1 {
+            row.push_str(msg.topic);
+            row.push_str(" -> ");
+        }
+        if app_matches.is_present("timestamp") {
+            row.push('[');
+            row.push_str(
+                &msg.timestamp
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or_else(|| String::from("NO-TS")),
+            );
+            row.push_str("] ");
+        }
+        if app_matches.is_present("headers") && !msg.headers.is_empty() {
+            row.push('@');
+            row.push_str(
+                &msg.headers
+                    .iter()
+                    .map(|h| h.to_string())
+                    .collect::<Vec<String>>()
+                    .join(","),
+            );
+            row.push(' ');
+        }
+        if app_matches.is_present("key-separator") {
+            row.push_str(
+                &msg.key
+                    .as_ref()
+                    .map_or(String::from("null"), |key| key.to_string()),
+            );
+            row.push_str(app_matches.value_of("key-separator").unwrap());
+        }
+        row.push_str(&msg.payload.to_string());
+        row
+    }
+
+    fn extract_position_from_arg(
+        app_matches: &ArgMatches<'_>,
+    ) -> Result<Option<KafkyConsumerPosition>, KafkyError> {
+        if let Some(from_time) = app_matches.value_of("from-time") {
+            return Ok(Some(KafkyConsumerPosition::Timestamp(
+                Self::parse_time(from_time)?.timestamp_millis(),
+            )));
+        }
+        if let Some(since) = app_matches.value_of("since") {
+            let duration = humantime::parse_duration(since)
+                .map_err(|e| KafkyError::InvalidTime(since.to_string(), e.to_string()))?;
+            let since_millis = i64::try_from(duration.as_millis())
+                .map_err(|e| KafkyError::InvalidTime(since.to_string(), e.to_string()))?;
+            return Ok(Some(KafkyConsumerPosition::Timestamp(
+                Utc::now().timestamp_millis() - since_millis,
+            )));
+        }
+        if let Some(last) = app_matches.value_of("last") {
+            return Self::parse_offset(last)
+                .filter(|last| *last > 0)
+                .map(|last| Some(KafkyConsumerPosition::Last(last)))
+                .ok_or_else(|| KafkyError::ParseError(format!("invalid --last value {}", last)));
+        }
+        if let Some(offsets) = app_matches.values_of("partition-offset") {
+            let mut default = None;
+            let mut partitions = BTreeMap::new();
+            for offset in offsets {
+                let invalid_offset = || KafkyError::InvalidPartitionOffset(offset.to_string());
+                match offset.split_once(':') {
+                    Some((partition, partition_offset)) => {
+                        partitions.insert(
+                            partition.parse::<i32>().map_err(|_| invalid_offset())?,
+                            Self::parse_offset(partition_offset).ok_or_else(invalid_offset)?,
+                        );
+                    }
+                    None if default.is_none() => {
+                        default = Some(Self::parse_offset(offset).ok_or_else(invalid_offset)?)
+                    }
+                    None => return Err(invalid_offset()),
+                }
+            }
+            return Ok(Some(KafkyConsumerPosition::Offsets {
+                default,
+                partitions,
+            }));
+        }
+        Ok(None)
+    }
+
+    fn parse_offset(offset: &str) -> Option<i64> {
+        offset.parse::<i64>().ok().filter(|offset| *offset >= 0)
+    }
+
+    fn extract_partitions_from_arg(
+        app_matches: &ArgMatches<'_>,
+    ) -> Result<Option<Vec<i32>>, KafkyError> {
+        app_matches
+            .values_of("partition")
+            .map(|partitions| {
+                partitions
+                    .map(|partition| {
+                        partition.parse::<i32>().map_err(|e| {
+                            KafkyError::ParseError(format!("partition {}: {}", partition, e))
+                        })
+                    })
+                    .collect()
+            })
+            .transpose()
+    }
+
+    fn parse_time(time: &str) -> Result<DateTime<Utc>, KafkyError> {
+        DateTime::parse_from_rfc3339(time)
+            .map(|t| t.with_timezone(&Utc))
+            .map_err(|e| KafkyError::InvalidTime(time.to_string(), e.to_string()))
     }
 
     fn extract_offset_from_arg(
@@ -135,3 +487,66 @@ impl ConsumeCmd {
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(args: &[&str]) -> Result<Option<KafkyConsumerPosition>, KafkyError> {
+        let matches =
+            ConsumeCmd::command().get_matches_from([&["consume", "-t", "topic"], args].concat());
+        ConsumeCmd::extract_position_from_arg(&matches)
+    }
+
+    #[test]
+    fn max_messages_test() -> Result<(), KafkyError> {
+        let max_messages = |args: &[&str]| {
+            let matches = ConsumeCmd::command()
+                .get_matches_from([&["consume", "-t", "topic"], args].concat());
+            ConsumeCmd::extract_max_messages(&matches)
+        };
+        assert_eq!(max_messages(&[])?, None);
+        assert_eq!(max_messages(&["-n", "5"])?, Some(5));
+        assert!(matches!(
+            max_messages(&["--max-messages", "0"]),
+            Err(KafkyError::ParseError(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn position_test() -> Result<(), KafkyError> {
+        assert_eq!(position(&[])?, None);
+        assert_eq!(
+            position(&["--from-time", "2026-10-01T10:00:00Z"])?,
+            Some(KafkyConsumerPosition::Timestamp(1790848800000))
+        );
+        match position(&["--since", "15m"])? {
+            Some(KafkyConsumerPosition::Timestamp(timestamp)) => {
+                let expected = Utc::now().timestamp_millis() - 15 * 60 * 1000;
+                assert!((expected - timestamp).abs() < 60 * 1000);
+            }
+            other => panic!("timestamp position expected, got {:?}", other),
+        }
+        assert!(matches!(
+            position(&["--from-time", "yesterday"]),
+            Err(KafkyError::InvalidTime(_, _))
+        ));
+        assert_eq!(
+            position(&["--offset", "10", "--offset", "2:42"])?,
+            Some(KafkyConsumerPosition::Offsets {
+                default: Some(10),
+                partitions: BTreeMap::from([(2, 42)])
+            })
+        );
+        assert_eq!(
+            position(&["--last", "20"])?,
+            Some(KafkyConsumerPosition::Last(20))
+        );
+        assert!(matches!(
+            position(&["--offset", "2:-1"]),
+            Err(KafkyError::InvalidPartitionOffset(_))
+        ));
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+use crate::cmd::root::RootCmd;
+use crate::config::KafkyEnvironment;
 use crate::{KafkyClient, KafkyError};
 use clap::{App, Arg, ArgMatches, SubCommand};
 
@@ -42,6 +44,7 @@ impl CreateCmd {
     pub(super) async fn exec<'a>(
         arg_matches: &'a ArgMatches<'a>,
         kafky_client: &'a KafkyClient<'a>,
+        environment: &KafkyEnvironment,
     ) -> Result<(), KafkyError> {
         if let Some(create_topic_args) = arg_matches.subcommand_matches("topics") {
             let topic_names: Vec<&str> = create_topic_args.values_of("topic").unwrap().collect();
@@ -56,6 +59,10 @@ impl CreateCmd {
                 .parse()
                 .expect("invalid replication-factor value");
 
+            RootCmd::confirm_protected(
+                environment,
+                &format!("create the {} topics", topic_names.join(", ")),
+            )?;
             let metadata = kafky_client.get_metadata(None)?;
             let existent_topic_names: Vec<String> = metadata.topic_names();
             let topics_to_create: Vec<&str> = topic_names
@@ -1,3 +1,5 @@
+use crate::cmd::root::RootCmd;
+use crate::config::KafkyEnvironment;
 use crate::{KafkyClient, KafkyError};
 use clap::{App, Arg, ArgMatches, SubCommand};
 use std::io::stdin;
@@ -32,11 +34,17 @@ impl DeleteCmd {
     pub(super) async fn exec<'a>(
         arg_matches: &'a ArgMatches<'a>,
         kafky_client: &'a KafkyClient<'a>,
+        environment: &KafkyEnvironment,
     ) -> Result<(), KafkyError> {
         if let Some(create_topic_args) = arg_matches.subcommand_matches("topics") {
             let topic_names: Vec<&str> = create_topic_args.values_of("topic").unwrap().collect();
             let auto_yes = create_topic_args.is_present("yes");
-            if !auto_yes {
+            if environment.protected {
+                RootCmd::confirm_protected(
+                    environment,
+                    &format!("delete the {} topics", topic_names.join(", ")),
+                )?;
+            } else if !auto_yes {
                 println!(
                     "Are you sure that you want to delete the {} topics? [y/N]",
                     topic_names.join(", ")
@@ -0,0 +1,344 @@
+use std::cmp::Ordering;
+
+use chrono::DateTime;
+use regex::Regex;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+use crate::client::encoding::KafkyEncodedData;
+use crate::cmd::consume::KafkyRenderedMessage;
+use crate::errors::KafkyError;
+
+/// `<field> <operator> <value>` over a consumed message
+#[derive(Debug)]
+pub(super) struct KafkyFilter {
+    field: KafkyFilterField,
+    operator: KafkyFilterOperator,
+    operand: KafkyFilterOperand,
+}
+
+#[derive(Debug)]
+enum KafkyFilterField {
+    Key,
+    Payload,
+    Header(String),
+    Partition,
+    Offset,
+    Timestamp,
+    /// over the payload parsed as JSON, e.g. `$.order.status`
+    JsonPath(JsonPath),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KafkyFilterOperator {
+    Eq,
+    Ne,
+    Match,
+    NotMatch,
+    Contains,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+#[derive(Debug)]
+enum KafkyFilterOperand {
+    Regex(Regex),
+    Value(Value),
+}
+
+const OPERATORS: [(&str, KafkyFilterOperator); 9] = [
+    ("==", KafkyFilterOperator::Eq),
+    ("!=", KafkyFilterOperator::Ne),
+    ("=~", KafkyFilterOperator::Match),
+    ("!~", KafkyFilterOperator::NotMatch),
+    (">=", KafkyFilterOperator::Ge),
+    ("<=", KafkyFilterOperator::Le),
+    (">", KafkyFilterOperator::Gt),
+    ("<", KafkyFilterOperator::Lt),
+    ("contains", KafkyFilterOperator::Contains),
+];
+
+impl KafkyFilter {
+    pub(super) fn parse(expression: &str) -> Result<KafkyFilter, KafkyError> {
+        let invalid =
+            |reason: &str| KafkyError::InvalidFilter(expression.to_string(), reason.to_string());
+        let expression = expression.trim();
+        let field_end = Self::field_end(expression);
+        let (field, rest) = expression.split_at(field_end);
+        let rest = rest.trim_start();
+        let (operator_str, operator) = OPERATORS
+            .iter()
+            .find(|(operator_str, _)| rest.starts_with(operator_str))
+            .ok_or_else(|| invalid("expected one of ==, !=, =~, !~, >, >=, <, <=, contains"))?;
+        let value = rest[operator_str.len()..].trim();
+        if value.is_empty() {
+            return Err(invalid("missing value"));
+        }
+
+        let field = match field {
+            "key" => KafkyFilterField::Key,
+            "payload" | "value" => KafkyFilterField::Payload,
+            "partition" => KafkyFilterField::Partition,
+            "offset" => KafkyFilterField::Offset,
+            "timestamp" => KafkyFilterField::Timestamp,
+            _ if field.starts_with('$') => KafkyFilterField::JsonPath(
+                JsonPath::parse(field).map_err(|e| invalid(&e.to_string()))?,
+            ),
+            _ => match field.strip_prefix("header.") {
+                Some(header) if !header.is_empty() => KafkyFilterField::Header(header.to_string()),
+                _ => return Err(invalid(
+                    "expected key, payload, header.<name>, partition, offset, timestamp or a $ JSON path",
+                )),
+            },
+        };
+
+        let operand = match operator {
+            KafkyFilterOperator::Match | KafkyFilterOperator::NotMatch => {
+                let pattern = match value.strip_prefix('/').and_then(|v| v.strip_suffix('/')) {
+                    Some(pattern) => pattern.to_string(),
+                    None => Self::text(&Self::literal(value)),
+                };
+                KafkyFilterOperand::Regex(
+                    Regex::new(&pattern).map_err(|e| invalid(&e.to_string()))?,
+                )
+            }
+            _ => KafkyFilterOperand::Value(match field {
+                KafkyFilterField::Partition | KafkyFilterField::Offset => value
+                    .parse::<i64>()
+                    .map(Value::from)
+                    .map_err(|_| invalid("expected an integer"))?,
+                KafkyFilterField::Timestamp => {
+                    DateTime::parse_from_rfc3339(&Self::text(&Self::literal(value)))
+                        .map(|t| Value::from(t.timestamp_millis()))
+                        .map_err(|e| invalid(&e.to_string()))?
+                }
+                _ => Self::literal(value),
+            }),
+        };
+        Ok(KafkyFilter {
+            field,
+            operator: *operator,
+            operand,
+        })
+    }
+
+    /// the field ends at the first space or operator outside of brackets and quotes
+    fn field_end(expression: &str) -> usize {
+        let mut depth = 0;
+        let mut quote = None;
+        for (idx, c) in expression.char_indices() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"') => quote = Some(c),
+                (None, '[' | '(') => depth += 1,
+                (None, ']' | ')') => depth -= 1,
+                (None, c) if depth == 0 && (c.is_whitespace() || "=!~<>".contains(c)) => {
+                    return idx
+                }
+                _ => {}
+            }
+        }
+        expression.len()
+    }
+
+    /// JSON literal, e.g. `"FAILED"`, `42` or `true`, the text itself otherwise
+    fn literal(value: &str) -> Value {
+        serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()))
+    }
+
+    fn text(value: &Value) -> String {
+        match value {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        }
+    }
+
+    pub(super) fn matches(&self, msg: &KafkyRenderedMessage) -> bool {
+        let actual = match &self.field {
+            KafkyFilterField::Key => msg.key.as_ref().map(Self::encoded_value),
+            KafkyFilterField::Payload => Some(Self::encoded_value(&msg.payload)),
+            KafkyFilterField::Header(name) => msg
+                .headers
+                .iter()
+                .find(|header| &header.key == name)
+                .map(|header| Value::String(header.value.clone().unwrap_or_default())),
+            KafkyFilterField::Partition => Some(Value::from(msg.partition)),
+            KafkyFilterField::Offset => Some(Value::from(msg.offset)),
+            KafkyFilterField::Timestamp => msg.timestamp.map(|t| Value::from(t.timestamp_millis())),
+            KafkyFilterField::JsonPath(path) => msg
+                .json_payload()
+                .and_then(|payload| path.query(payload).first().cloned()),
+        };
+        // a missing key, header or JSON node only matches the negative operators
+        let actual = match actual {
+            Some(actual) => actual,
+            None => {
+                return matches!(
+                    self.operator,
+                    KafkyFilterOperator::Ne | KafkyFilterOperator::NotMatch
+                )
+            }
+        };
+        match (&self.operand, self.operator) {
+            (KafkyFilterOperand::Regex(regex), KafkyFilterOperator::NotMatch) => {
+                !regex.is_match(&Self::text(&actual))
+            }
+            (KafkyFilterOperand::Regex(regex), _) => regex.is_match(&Self::text(&actual)),
+            (KafkyFilterOperand::Value(value), operator) => match operator {
+                KafkyFilterOperator::Contains => Self::text(&actual).contains(&Self::text(value)),
+                KafkyFilterOperator::Eq => Self::equals(&actual, value),
+                KafkyFilterOperator::Ne => !Self::equals(&actual, value),
+                _ => Self::compare(&actual, value).is_some_and(|ordering| match operator {
+                    KafkyFilterOperator::Gt => ordering == Ordering::Greater,
+                    KafkyFilterOperator::Ge => ordering != Ordering::Less,
+                    KafkyFilterOperator::Lt => ordering == Ordering::Less,
+                    _ => ordering != Ordering::Greater,
+                }),
+            },
+        }
+    }
+
+    fn encoded_value(data: &KafkyEncodedData) -> Value {
+        match data {
+            KafkyEncodedData::Json(json) => json.clone(),
+            text => Value::String(text.to_string()),
+        }
+    }
+
+    /// `"42"` equals `42`: keys, payloads and headers are text
+    fn equals(actual: &Value, value: &Value) -> bool {
+        actual == value || Self::text(actual) == Self::text(value)
+    }
+
+    /// numbers by value, anything else as text
+    fn compare(actual: &Value, value: &Value) -> Option<Ordering> {
+        match (Self::number(actual), Self::number(value)) {
+            (Some(actual), Some(value)) => actual.partial_cmp(&value),
+            _ => Some(Self::text(actual).cmp(&Self::text(value))),
+        }
+    }
+
+    fn number(value: &Value) -> Option<f64> {
+        match value {
+            Value::Number(number) => number.as_f64(),
+            Value::String(text) => text.parse::<f64>().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// scanned and printed messages, the ones matching the filters if any: `max_matched` is `--max-messages`
+#[derive(Default)]
+pub(super) struct KafkyFilterStats {
+    pub(super) scanned: u64,
+    matched: u64,
+    max_matched: Option<u64>,
+}
+
+impl KafkyFilterStats {
+    pub(super) fn new(max_matched: Option<u64>) -> Self {
+        KafkyFilterStats {
+            max_matched,
+            ..Default::default()
+        }
+    }
+
+    /// counts a matching message, returns whether more are wanted
+    pub(super) fn matched(&mut self) -> bool {
+        self.matched += 1;
+        self.max_matched.is_none_or(|max| self.matched < max)
+    }
+
+    pub(super) fn print(&self) {
+        eprintln!(
+            "{} messages scanned, {} matched",
+            self.scanned, self.matched
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::consumer::KafkyHeader;
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+    use std::cell::OnceCell;
+
+    fn matches(expression: &str, msg: &KafkyRenderedMessage) -> bool {
+        KafkyFilter::parse(expression).unwrap().matches(msg)
+    }
+
+    #[test]
+    fn filter_test() {
+        let headers = vec![KafkyHeader {
+            key: "tenant".to_string(),
+            value: Some("acme".to_string()),
+        }];
+        let msg = KafkyRenderedMessage {
+            key: Some(KafkyEncodedData::Utf8("order-42".to_string())),
+            topic: "orders",
+            payload: KafkyEncodedData::Utf8(
+                r#"{"order": {"status": "FAILED", "amount": 12.5}}"#.to_string(),
+            ),
+            partition: 3,
+            offset: 1200,
+            timestamp: Utc.timestamp_millis_opt(1790848800000).single(),
+            headers: &headers,
+            parsed_payload: OnceCell::new(),
+        };
+        assert!(matches(r#"$.order.status == "FAILED""#, &msg));
+        assert!(!matches(r#"$.order.status != "FAILED""#, &msg));
+        assert!(matches("$.order.amount > 10", &msg));
+        assert!(matches("$.order.missing != 1", &msg));
+        assert!(!matches("$.order.missing == 1", &msg));
+        assert!(matches("key =~ /^order-\\d+$/", &msg));
+        assert!(matches("key !~ ^invoice", &msg));
+        assert!(matches("payload contains FAILED", &msg));
+        assert!(matches(r#"header.tenant == "acme""#, &msg));
+        assert!(!matches("header.region == eu", &msg));
+        assert!(matches("partition == 3", &msg));
+        assert!(matches("offset>=1200", &msg));
+        assert!(matches("timestamp < 2026-10-01T10:00:01Z", &msg));
+        // parsed by the first JSON path filter, reused by the next ones
+        assert!(msg.parsed_payload.get().is_some_and(|json| json.is_some()));
+
+        let avro = KafkyRenderedMessage {
+            payload: KafkyEncodedData::Json(json!({"order": {"status": "OK"}})),
+            parsed_payload: OnceCell::new(),
+            ..msg
+        };
+        assert!(matches(r#"$['order'].status == "OK""#, &avro));
+
+        for invalid in [
+            "key",
+            "topic == orders",
+            "offset > last",
+            "key =~ (",
+            "$.[ == 1",
+        ] {
+            assert!(
+                matches!(
+                    KafkyFilter::parse(invalid),
+                    Err(KafkyError::InvalidFilter(_, _))
+                ),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn stats_test() {
+        let mut stats = KafkyFilterStats::new(Some(2));
+        assert!(stats.matched());
+        assert!(!stats.matched());
+
+        let mut unlimited = KafkyFilterStats::new(None);
+        assert!(unlimited.matched());
+        assert_eq!(unlimited.matched, 1);
+    }
+}
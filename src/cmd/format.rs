@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::{App, Arg, ArgMatches};
+use prost_reflect::MessageDescriptor;
+
+use crate::client::format::KafkyFormat;
+use crate::client::protobuf::KafkyProtoSchema;
+use crate::errors::KafkyError;
+
+/// protobuf descriptors options shared by consume and produce
+pub(super) fn protobuf_args<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
+    app.arg(
+        Arg::with_name("proto-path")
+            .long("proto-path")
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("DIR")
+            .help("directory of .proto files, also used as import root"),
+    )
+    .arg(
+        Arg::with_name("descriptor-set")
+            .long("descriptor-set")
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("FILE")
+            .help("compiled FileDescriptorSet, e.g. from protoc --include_imports -o"),
+    )
+    .arg(
+        Arg::with_name("message-type")
+            .long("message-type")
+            .value_name("TYPE")
+            .help("protobuf message type of the values, e.g. shop.Order"),
+    )
+    .arg(
+        Arg::with_name("key-message-type")
+            .long("key-message-type")
+            .value_name("TYPE")
+            .help("protobuf message type of the keys"),
+    )
+}
+
+pub(super) fn extract_format(
+    app_matches: &ArgMatches<'_>,
+    name: &str,
+) -> Result<KafkyFormat, KafkyError> {
+    let format = app_matches.value_of(name).unwrap();
+    KafkyFormat::from_str(format)
+        .map_err(|_| KafkyError::ParseError(format!("invalid {} {}", name, format)))
+}
+
+/// the key and value message types, loaded only for the protobuf formats
+pub(super) fn extract_messages(
+    app_matches: &ArgMatches<'_>,
+    key_format: KafkyFormat,
+    value_format: KafkyFormat,
+) -> Result<(Option<MessageDescriptor>, Option<MessageDescriptor>), KafkyError> {
+    if key_format != KafkyFormat::Protobuf && value_format != KafkyFormat::Protobuf {
+        return Ok((None, None));
+    }
+    let paths = |name| -> Vec<PathBuf> {
+        app_matches
+            .values_of(name)
+            .unwrap_or_default()
+            .map(PathBuf::from)
+            .collect()
+    };
+    let (proto_paths, descriptor_sets) = (paths("proto-path"), paths("descriptor-set"));
+    if proto_paths.is_empty() && descriptor_sets.is_empty() {
+        return Err(KafkyError::ProtobufError(
+            "--proto-path or --descriptor-set is required by the protobuf format".to_string(),
+        ));
+    }
+    let schema = KafkyProtoSchema::load(&proto_paths, &descriptor_sets)?;
+    let message = |format, name| -> Result<Option<MessageDescriptor>, KafkyError> {
+        if format != KafkyFormat::Protobuf {
+            return Ok(None);
+        }
+        let message_type = app_matches.value_of(name).ok_or_else(|| {
+            KafkyError::ProtobufError(format!("--{} is required by the protobuf format", name))
+        })?;
+        schema.message(message_type).map(Some)
+    };
+    Ok((
+        message(key_format, "key-message-type")?,
+        message(value_format, "message-type")?,
+    ))
+}
@@ -98,7 +98,7 @@ impl GetConsumerGroupsCmd {
                         topics_tpl.1.iter().map(|partition_tpl| {
                             let mut cache = latest_offset_map_mtx.lock().unwrap();
                             let latest_offset = Self::latest_offset(
-                                &mut *cache,
+                                &mut cache,
                                 topics_tpl.0,
                                 partition_tpl.0,
                                 kafky_client,
@@ -125,9 +125,9 @@ impl GetConsumerGroupsCmd {
         Ok(())
     }
 
-    fn latest_offset<'b, 'c>(
-        latest_offset_map: &'b mut HashMap<(String, i32), i64>,
-        topic: &'c str,
+    fn latest_offset(
+        latest_offset_map: &mut HashMap<(String, i32), i64>,
+        topic: &str,
         partition: &i32,
         kafky_client: &KafkyClient,
     ) -> i64 {
@@ -1,7 +1,10 @@
 pub mod config;
+mod config_manage;
 mod consume;
 mod create;
 mod delete;
+mod filter;
+mod format;
 mod get;
 mod get_consumer_groups;
 mod get_topic;
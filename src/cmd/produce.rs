@@ -7,8 +7,12 @@ use clap::{App, Arg, ArgMatches, SubCommand};
 use log::{debug, error};
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
+use strum::VariantNames;
 
+use crate::client::consumer::KafkyHeader;
+use crate::client::format::{KafkyEncoder, KafkyFormat};
 use crate::client::kafky_client::KafkyClient;
+use crate::cmd::format;
 use crate::errors::KafkyError;
 use crate::KafkyConfig;
 
@@ -16,7 +20,7 @@ pub struct ProduceCmd {}
 
 impl ProduceCmd {
     pub fn command<'a>() -> App<'a, 'a> {
-        SubCommand::with_name("produce")
+        let produce = SubCommand::with_name("produce")
             .about("Produce messages to a topic")
             .arg(
                 Arg::with_name("topic")
@@ -32,6 +36,59 @@ impl ProduceCmd {
                     .short("k")
                     .takes_value(true),
             )
+            .arg(
+                Arg::with_name("header")
+                    .long("header")
+                    .short("H")
+                    .multiple(true)
+                    .number_of_values(1)
+                    .value_name("KEY=VALUE")
+                    .help("header added to every message, a line starting with @k1=v1,k2=v2 followed by a space adds its own"),
+            )
+            .arg(
+                Arg::with_name("key-format")
+                    .long("key-format")
+                    .possible_values(KafkyFormat::VARIANTS)
+                    .default_value("raw")
+                    .help("format of the keys, avro and protobuf encode JSON keys, json validates them with the JSON Schema of the subject"),
+            )
+            .arg(
+                Arg::with_name("value-format")
+                    .long("value-format")
+                    .possible_values(KafkyFormat::VARIANTS)
+                    .default_value("raw")
+                    .help("format of the values, avro and protobuf encode JSON values, json validates them with the JSON Schema of the subject"),
+            )
+            .arg(
+                Arg::with_name("key-subject")
+                    .long("key-subject")
+                    .value_name("SUBJECT")
+                    .help("schema registry subject of the keys, <topic>-key by default"),
+            )
+            .arg(
+                Arg::with_name("value-subject")
+                    .long("value-subject")
+                    .value_name("SUBJECT")
+                    .help("schema registry subject of the values, <topic>-value by default"),
+            )
+            .arg(
+                Arg::with_name("key-schema-version")
+                    .long("key-schema-version")
+                    .value_name("VERSION")
+                    .help("version of the key subject, the latest by default"),
+            )
+            .arg(
+                Arg::with_name("value-schema-version")
+                    .long("value-schema-version")
+                    .value_name("VERSION")
+                    .help("version of the value subject, the latest by default"),
+            )
+            .arg(
+                Arg::with_name("wire-format")
+                    .long("wire-format")
+                    .help("write the Confluent wire format header with the schema id of the subject for protobuf and json, always on for avro"),
+            );
+        format::protobuf_args(produce)
     }
     pub async fn exec<'a>(
         app_matches: &'a ArgMatches<'a>,
@@ -46,6 +103,43 @@ impl ProduceCmd {
         }
 
         let key_separator_opt = app_matches.value_of("key-separator");
+        let headers = app_matches
+            .values_of("header")
+            .unwrap_or_default()
+            .map(KafkyHeader::parse)
+            .collect::<Result<Vec<KafkyHeader>, KafkyError>>()?;
+        let key_format = format::extract_format(app_matches, "key-format")?;
+        let value_format = format::extract_format(app_matches, "value-format")?;
+        let wire_format = app_matches.is_present("wire-format");
+        let uses_registry = |format| match format {
+            KafkyFormat::Avro => true,
+            KafkyFormat::Protobuf => wire_format,
+            KafkyFormat::Json => true,
+            KafkyFormat::Raw => false,
+        };
+        let registry = if uses_registry(key_format) || uses_registry(value_format) {
+            Some(kafky_client.schema_registry()?)
+        } else {
+            None
+        };
+        let (key_message, value_message) =
+            format::extract_messages(app_matches, key_format, value_format)?;
+        let key_encoder = KafkyEncoder::new(
+            key_format,
+            registry.as_ref(),
+            &Self::extract_subject(app_matches, topic, "key"),
+            Self::extract_schema_version(app_matches, "key-schema-version")?,
+            key_message,
+            wire_format,
+        )?;
+        let value_encoder = KafkyEncoder::new(
+            value_format,
+            registry.as_ref(),
+            &Self::extract_subject(app_matches, topic, "value"),
+            Self::extract_schema_version(app_matches, "value-schema-version")?,
+            value_message,
+            wire_format,
+        )?;
 
         let mut editor = Editor::<()>::new();
 
@@ -66,11 +160,30 @@ impl ProduceCmd {
             io::stdout().flush().unwrap();
             match editor.readline(&format!("{} <- ", topic)) {
                 Ok(read_line) => {
-                    if let Ok((key, payload)) =
-                        Self::extract_key_payload(key_separator_opt, &read_line)
+                    if let Ok((line_headers, key, payload)) =
+                        Self::extract_message(key_separator_opt, &read_line)
                     {
                         editor.add_history_entry(&read_line);
-                        match kafky_client.produce(topic, key, payload.to_string()) {
+                        let message_headers: Vec<KafkyHeader> =
+                            headers.iter().cloned().chain(line_headers).collect();
+                        let encoded = key
+                            .map(|key| key_encoder.encode(&key))
+                            .transpose()
+                            .map_err(|e| KafkyError::EncodeError("key".to_string(), e))
+                            .and_then(|key| {
+                                value_encoder
+                                    .encode(&payload)
+                                    .map(|payload| (key, payload))
+                                    .map_err(|e| KafkyError::EncodeError("value".to_string(), e))
+                            });
+                        let (key, payload) = match encoded {
+                            Ok(encoded) => encoded,
+                            Err(e) => {
+                                error!("{}", e);
+                                continue;
+                            }
+                        };
+                        match kafky_client.produce(topic, key, payload, &message_headers) {
                             Ok(_) => {
                                 debug!("message sent to topic {}", topic);
                             }
@@ -100,6 +213,56 @@ impl ProduceCmd {
         Ok(())
     }
 
+    fn extract_subject(app_matches: &ArgMatches<'_>, topic: &str, part: &str) -> String {
+        app_matches
+            .value_of(format!("{}-subject", part))
+            .map_or_else(|| format!("{}-{}", topic, part), |s| s.to_string())
+    }
+
+    fn extract_schema_version(
+        app_matches: &ArgMatches<'_>,
+        name: &str,
+    ) -> Result<Option<u32>, KafkyError> {
+        app_matches
+            .value_of(name)
+            .map(|version| {
+                version
+                    .parse::<u32>()
+                    .map_err(|e| KafkyError::ParseError(format!("{} {}: {}", name, version, e)))
+            })
+            .transpose()
+    }
+
+    /// the line is `[@k1=v1,k2=v2 ][key<separator>]payload`, a leading `@@` is a literal `@`
+    fn extract_message(
+        key_separator_opt: Option<&str>,
+        read_line: &str,
+    ) -> Result<(Vec<KafkyHeader>, Option<String>, String), KafkyError> {
+        if read_line.starts_with("@@") {
+            let (key, payload) = Self::extract_key_payload(key_separator_opt, &read_line[1..])?;
+            return Ok((vec![], key, payload));
+        }
+        let (headers, message) = match read_line
+            .strip_prefix('@')
+            .and_then(|line| line.split_once(' '))
+        {
+            Some((headers, message)) => (
+                headers
+                    .split(',')
+                    .map(KafkyHeader::parse)
+                    .collect::<Result<Vec<KafkyHeader>, KafkyError>>()
+                    .map_err(|e| {
+                        error!("{}", e);
+                        e
+                    })?,
+                message,
+            ),
+            None => (vec![], read_line),
+        };
+        let (key, payload) = Self::extract_key_payload(key_separator_opt, message)?;
+        Ok((headers, key, payload))
+    }
+
     fn extract_key_payload(
         key_separator_opt: Option<&str>,
         read_line: &str,
@@ -111,7 +274,7 @@ impl ProduceCmd {
                 return Err(KafkyError::KeySeparatorNotFound());
             }
             Ok((
-                key_payload.get(0).map(|k| k.to_string()),
+                key_payload.first().map(|k| k.to_string()),
                 key_payload.get(1).map(|s| s.to_string()).unwrap(),
             ))
         } else {
@@ -119,3 +282,41 @@ impl ProduceCmd {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_message_test() -> Result<(), KafkyError> {
+        let (headers, key, payload) =
+            ProduceCmd::extract_message(Some("::"), "@trace-id=42,empty key::{\"a\": 1}")?;
+        assert_eq!(
+            headers,
+            vec![
+                KafkyHeader {
+                    key: "trace-id".to_string(),
+                    value: Some("42".to_string())
+                },
+                KafkyHeader {
+                    key: "empty".to_string(),
+                    value: None
+                }
+            ]
+        );
+        assert_eq!(key, Some("key".to_string()));
+        assert_eq!(payload, "{\"a\": 1}");
+
+        let (headers, key, payload) = ProduceCmd::extract_message(None, "hello world")?;
+        assert!(headers.is_empty());
+        assert_eq!(key, None);
+        assert_eq!(payload, "hello world");
+
+        let (headers, key, payload) =
+            ProduceCmd::extract_message(Some("::"), "@@user::@mention of someone")?;
+        assert!(headers.is_empty());
+        assert_eq!(key, Some("@user".to_string()));
+        assert_eq!(payload, "@mention of someone");
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
-use crate::config::KafkyConfig;
+use crate::config::properties::parse_property;
+use crate::config::{KafkyConfig, KafkyEnvironment};
 use clap::{App, Arg, ArgMatches};
 use log::debug;
+use std::io::stdin;
 use tokio::signal;
 
 use crate::cmd::config::ConfigCmd;
@@ -32,15 +34,33 @@ impl RootCmd {
                     .long("environment")
                     .short("e")
                     .possible_values(environments.as_slice())
+                    .env("KAFKY_ENVIRONMENT")
                     .value_name("STRING")
-                    .help("environment"),
+                    .help("environment, default the configuration defaultEnvironment"),
             )
             .arg(
                 Arg::with_name("credential")
                     .long("credential")
                     .short("c")
+                    .env("KAFKY_CREDENTIAL")
                     .value_name("STRING")
-                    .help("environment"),
+                    .help("credential, default the environment defaultCredential or its only credential"),
+            )
+            .arg(
+                Arg::with_name("config")
+                    .long("config")
+                    .takes_value(true)
+                    .value_name("PATH")
+                    .help("configuration file, default $KAFKY_CONFIG or ~/.kafky/config.yml"),
+            )
+            .arg(
+                Arg::with_name("property")
+                    .short("X")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .value_name("KEY=VALUE")
+                    .help("librdkafka property, overriding the environment and credential ones"),
             )
             .subcommand(ConsumeCmd::command())
             .subcommand(GetCmd::command())
@@ -61,25 +81,47 @@ impl RootCmd {
         }
         debug!("sub command:{:?}", sub_command_tpl);
         if sub_command_tpl.0 == "config" {
-            return ConfigCmd::exec(sub_command_tpl.1.unwrap(), config.path());
+            return ConfigCmd::exec(sub_command_tpl.1.unwrap(), config);
         }
-        if !app_matches.is_present("environment") {
-            return Err(KafkyError::EnvironmentParamNotFound());
-        }
-        let environment = String::from(app_matches.value_of("environment").unwrap());
+        let environment = app_matches
+            .value_of("environment")
+            .map(|e| e.to_string())
+            .or_else(|| config.default_environment.clone())
+            .ok_or_else(KafkyError::EnvironmentParamNotFound)?;
         let credential = Self::extract_credential(&app_matches, config, &environment)?;
+        let kafky_environment = config.get_environment(&environment).ok_or_else(|| {
+            KafkyError::EnvironmentNotFound(
+                environment.clone(),
+                config.get_environment_names().join(","),
+            )
+        })?;
+        // the whole configuration is checked by `config validate`
+        config.check_environment(&environment, &credential)?;
+        if let Some(action) = Self::write_action(sub_command_tpl) {
+            kafky_environment.check_writable(action)?;
+        }
 
-        let kafky_client = KafkyClient::new(config, &environment, &credential);
-        let close_rx = Self::termination_receiver();
+        let properties = app_matches
+            .values_of("property")
+            .unwrap_or_default()
+            .map(parse_property)
+            .collect::<Result<Vec<(String, String)>, KafkyError>>()?;
+        let kafky_client =
+            KafkyClient::new(config, &environment, &credential).with_properties(properties);
+        // the consumer stops by itself on ctrl+c, to print its filter stats
+        let (close_rx, consume_close_rx) = match sub_command_tpl {
+            ("consume", _) => (None, Some(Self::termination_receiver())),
+            _ => (Some(Self::termination_receiver()), None),
+        };
 
         tokio::select! {
             result = async {
                 let res:Result<(),KafkyError> = match sub_command_tpl {
                     ("get", Some(matches)) => GetCmd::exec(matches, &kafky_client).await,
                     ("produce", Some(matches)) => ProduceCmd::exec(matches, &kafky_client,config,&environment).await,
-                    ("consume", Some(matches)) => ConsumeCmd::exec(matches, &kafky_client).await,
-                    ("create", Some(matches)) => CreateCmd::exec(matches, &kafky_client).await,
-                    ("delete", Some(matches)) => DeleteCmd::exec(matches, &kafky_client).await,
+                    ("consume", Some(matches)) => ConsumeCmd::exec(matches, &kafky_client, consume_close_rx.unwrap()).await,
+                    ("create", Some(matches)) => CreateCmd::exec(matches, &kafky_client, kafky_environment).await,
+                    ("delete", Some(matches)) => DeleteCmd::exec(matches, &kafky_client, kafky_environment).await,
                     (_, _) => Err(KafkyError::InvalidCommand()),
                 };
                 res
@@ -95,13 +137,46 @@ impl RootCmd {
                     Ok(())
                 }
             },
-            _ = close_rx => {
+            Some(_) = async { match close_rx { Some(close_rx) => Some(close_rx.await), None => None } } => {
                 println!("Exiting...");
                 Ok(())
             }
         }
     }
 
+    /// the commands changing topics, messages or committed offsets, blocked on read-only environments
+    fn write_action(sub_command: (&str, Option<&ArgMatches>)) -> Option<&'static str> {
+        match sub_command {
+            ("produce", _) => Some("produce"),
+            ("create", _) => Some("create"),
+            ("delete", _) => Some("delete"),
+            ("consume", Some(matches)) if matches.is_present("autocommit") => {
+                Some("consume --autocommit")
+            }
+            _ => None,
+        }
+    }
+
+    /// on protected environments the user must type the environment name, `-y` flags don't apply
+    pub(super) fn confirm_protected(
+        environment: &KafkyEnvironment,
+        action: &str,
+    ) -> Result<(), KafkyError> {
+        if !environment.protected {
+            return Ok(());
+        }
+        println!(
+            "The environment {} is protected, type its name to {}:",
+            environment.name, action
+        );
+        let mut answer = String::new();
+        stdin().read_line(&mut answer)?;
+        if answer.trim() != environment.name {
+            return Err(KafkyError::ProtectedEnvironment(environment.name.clone()));
+        }
+        Ok(())
+    }
+
     fn termination_receiver() -> Receiver<bool> {
         let (close_tx, close_rx) = oneshot::channel();
         tokio::spawn(async move {
@@ -123,7 +198,9 @@ impl RootCmd {
             .map(|cred| cred.to_string())
             .or_else(|| {
                 config.get_environment(environment).and_then(|e| {
-                    if e.credentials.len() == 1 {
+                    if e.default_credential.is_some() {
+                        e.default_credential.clone()
+                    } else if e.credentials.len() == 1 {
                         let first_credential =
                             e.credentials.first().map(|c| c.name.clone()).unwrap();
                         Some(first_credential)
@@ -0,0 +1,320 @@
+use std::fs;
+
+use openssl::asn1::Asn1Time;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::x509::{X509NameRef, X509};
+use serde::Serialize;
+
+use crate::config::{
+    KafkyCredentialKind, KafkyEnvironment, KafkyPEM, KafkyPrivateKey, KafkySecretResolver,
+};
+
+impl KafkyPEM {
+    /// raw content of a path/base64/pem value, keystores have to be loaded with their password
+    pub fn read(&self) -> Result<Vec<u8>, String> {
+        match self {
+            KafkyPEM::Path(path) => {
+                fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))
+            }
+            KafkyPEM::Base64(b64) => {
+                base64::decode(b64).map_err(|e| format!("invalid base64: {}", e))
+            }
+            KafkyPEM::Pem(pem) => Ok(pem.as_bytes().to_vec()),
+            KafkyPEM::Pkcs12(keystore) | KafkyPEM::Jks(keystore) => keystore.store.read(),
+        }
+    }
+
+    /// certificates and, for keystores, the private key
+    fn load(
+        &self,
+        resolver: &KafkySecretResolver,
+    ) -> Result<(Vec<X509>, Option<PKey<Private>>), String> {
+        match self.keystore() {
+            None => {
+                let certificates = X509::stack_from_pem(&self.read()?)
+                    .map_err(|e| format!("invalid PEM certificate: {}", e))?;
+                if certificates.is_empty() {
+                    return Err("no certificate found".to_string());
+                }
+                Ok((certificates, None))
+            }
+            Some((format, keystore)) => {
+                let password = match &keystore.password {
+                    Some(password) => resolver.resolve(password)?,
+                    None => String::new(),
+                };
+                let key_password = match &keystore.key_password {
+                    Some(key_password) => Some(resolver.resolve(key_password)?),
+                    None => None,
+                };
+                let content = keystore.load(format, &password, key_password.as_deref())?;
+                let certificates = content
+                    .certificate
+                    .into_iter()
+                    .chain(content.ca_chain)
+                    .chain(content.trusted)
+                    .collect();
+                Ok((certificates, content.private_key))
+            }
+        }
+    }
+}
+
+impl KafkyPrivateKey {
+    fn load(&self, resolver: &KafkySecretResolver) -> Result<PKey<Private>, String> {
+        if self.key.keystore().is_some() {
+            return Err("keystores cannot be used as privateKey".to_string());
+        }
+        let pem = self.key.read()?;
+        match &self.password {
+            Some(password) => {
+                PKey::private_key_from_pem_passphrase(&pem, resolver.resolve(password)?.as_bytes())
+            }
+            None => PKey::private_key_from_pem(&pem),
+        }
+        .map_err(|e| format!("invalid private key: {}", e))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct KafkyCertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub sans: Vec<String>,
+    #[serde(rename = "notAfter")]
+    pub not_after: String,
+    #[serde(rename = "daysLeft")]
+    pub days_left: i32,
+}
+
+impl KafkyCertificateInfo {
+    fn new(certificate: &X509) -> Result<Self, String> {
+        let sans = certificate
+            .subject_alt_names()
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(|name| {
+                        name.dnsname()
+                            .map(|dns| format!("DNS:{}", dns))
+                            .or_else(|| name.email().map(|email| format!("email:{}", email)))
+                            .or_else(|| name.uri().map(|uri| format!("URI:{}", uri)))
+                            .or_else(|| name.ipaddress().map(|ip| format!("IP:{}", ip_string(ip))))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let days_left = Asn1Time::days_from_now(0)
+            .and_then(|now| now.diff(certificate.not_after()))
+            .map_err(|e| e.to_string())?
+            .days;
+        Ok(KafkyCertificateInfo {
+            subject: name_string(certificate.subject_name()),
+            issuer: name_string(certificate.issuer_name()),
+            sans,
+            not_after: certificate.not_after().to_string(),
+            days_left,
+        })
+    }
+}
+
+fn name_string(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let field = match entry.object().nid() {
+                Nid::UNDEF => entry.object().to_string(),
+                nid => nid.short_name().unwrap_or("?").to_string(),
+            };
+            format!("{}={}", field, entry.data().to_string().unwrap_or_default())
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn ip_string(ip: &[u8]) -> String {
+    match ip.len() {
+        4 => std::net::Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]).to_string(),
+        16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(ip);
+            std::net::Ipv6Addr::from(octets).to_string()
+        }
+        _ => hex_string(ip),
+    }
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Result of the inspection of a truststore or of a credential certificate
+#[derive(Debug, Serialize)]
+pub struct KafkyCertificateCheck {
+    pub environment: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential: Option<String>,
+    pub certificates: Vec<KafkyCertificateInfo>,
+    /// whether the private key matches the first certificate, only for credentials
+    #[serde(rename = "keyMatch", skip_serializing_if = "Option::is_none")]
+    pub key_match: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl KafkyCertificateCheck {
+    fn new(environment: &str, credential: Option<&str>) -> Self {
+        KafkyCertificateCheck {
+            environment: environment.to_string(),
+            credential: credential.map(|c| c.to_string()),
+            certificates: vec![],
+            key_match: None,
+            error: None,
+        }
+    }
+
+    fn inspect(
+        mut self,
+        pem: &KafkyPEM,
+        private_key: Option<&KafkyPrivateKey>,
+        resolver: &KafkySecretResolver,
+    ) -> Self {
+        if let Err(e) = self.inspect_certificates(pem, private_key, resolver) {
+            self.error = Some(e);
+        }
+        self
+    }
+
+    fn inspect_certificates(
+        &mut self,
+        pem: &KafkyPEM,
+        private_key: Option<&KafkyPrivateKey>,
+        resolver: &KafkySecretResolver,
+    ) -> Result<(), String> {
+        let (certificates, keystore_key) = pem.load(resolver)?;
+        for certificate in &certificates {
+            self.certificates
+                .push(KafkyCertificateInfo::new(certificate)?);
+        }
+        if self.credential.is_none() {
+            return Ok(());
+        }
+        let key = match private_key {
+            Some(private_key) => Some(private_key.load(resolver)?),
+            None => keystore_key,
+        };
+        if let (Some(key), Some(certificate)) = (key, certificates.first()) {
+            let public_key = certificate.public_key().map_err(|e| e.to_string())?;
+            self.key_match = Some(public_key.public_eq(&key));
+        }
+        Ok(())
+    }
+
+    /// true if the inspection failed, the key doesn't match or a certificate expires within `days`
+    pub fn is_failed(&self, days: i32) -> bool {
+        self.error.is_some()
+            || self.key_match == Some(false)
+            || self.certificates.iter().any(|c| c.days_left < days)
+    }
+}
+
+impl KafkyEnvironment {
+    /// inspects the truststore and the certificates of the ssl credentials
+    pub fn check_certificates(&self, resolver: &KafkySecretResolver) -> Vec<KafkyCertificateCheck> {
+        let mut checks = Vec::new();
+        if let Some(truststore) = &self.truststore {
+            checks.push(
+                KafkyCertificateCheck::new(&self.name, None).inspect(truststore, None, resolver),
+            );
+        }
+        for credential in &self.credentials {
+            if let KafkyCredentialKind::Ssl(ssl) = &credential.credential {
+                checks.push(
+                    KafkyCertificateCheck::new(&self.name, Some(&credential.name)).inspect(
+                        &ssl.certificate,
+                        ssl.private_key.as_ref(),
+                        resolver,
+                    ),
+                );
+            }
+        }
+        checks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::keystore::tests::self_signed;
+    use crate::config::KafkyConfig;
+    use indoc::indoc;
+    use std::io::Write;
+
+    #[test]
+    fn check_certificates_test() {
+        let (certificate, key) = self_signed("kafky-client");
+        let (_, other_key) = self_signed("other");
+        let pem = |bytes: Vec<u8>| base64::encode(bytes);
+        let mut tmp_cfg = tempfile::Builder::new().suffix(".yml").tempfile().unwrap();
+        write!(
+            tmp_cfg,
+            indoc! {"
+                environments:
+                  - name: test
+                    brokers:
+                      - localhost:9093
+                    truststore:
+                      base64: {certificate}
+                    credentials:
+                      - name: valid
+                        ssl:
+                          certificate:
+                            base64: {certificate}
+                          privateKey:
+                            base64: {key}
+                      - name: mismatch
+                        ssl:
+                          certificate:
+                            base64: {certificate}
+                          privateKey:
+                            base64: {other_key}
+                      - name: missing
+                        ssl:
+                          certificate:
+                            path: /not/existing.pem
+                          privateKey:
+                            base64: {key}
+            "},
+            certificate = pem(certificate.to_pem().unwrap()),
+            key = pem(key.private_key_to_pem_pkcs8().unwrap()),
+            other_key = pem(other_key.private_key_to_pem_pkcs8().unwrap()),
+        )
+        .unwrap();
+        let cfg = KafkyConfig::load(tmp_cfg.path()).unwrap();
+        let resolver = KafkySecretResolver::new(&cfg.vault_path());
+        let checks = cfg.environments[0].check_certificates(&resolver);
+        assert_eq!(checks.len(), 4);
+
+        let truststore = &checks[0];
+        assert_eq!(truststore.credential, None);
+        assert_eq!(truststore.certificates[0].subject, "CN=kafky-client");
+        assert_eq!(truststore.certificates[0].issuer, "CN=kafky-client");
+        assert_eq!(truststore.key_match, None);
+
+        let valid = &checks[1];
+        assert_eq!(valid.key_match, Some(true));
+        assert!((9..=10).contains(&valid.certificates[0].days_left));
+        assert!(!valid.is_failed(5));
+        assert!(valid.is_failed(30));
+
+        assert_eq!(checks[2].key_match, Some(false));
+        assert!(checks[2].is_failed(5));
+
+        assert!(checks[3]
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("/not/existing.pem"));
+        assert!(checks[3].is_failed(5));
+    }
+}
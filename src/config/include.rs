@@ -0,0 +1,237 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::debug;
+
+use crate::config::{KafkyConfig, KafkyEnvironment};
+use crate::errors::KafkyError;
+
+/// folder next to the main configuration file whose `*.yml`/`*.yaml` files are always included
+const CONFIG_DIR: &str = "config.d";
+
+impl<'a> KafkyConfig<'a> {
+    /// the `include` files followed by the `config.d` ones in alphabetical order
+    pub fn included_files(&self) -> Result<Vec<PathBuf>, KafkyError> {
+        let config_folder = self.config_folder();
+        let mut files: Vec<PathBuf> = self
+            .include
+            .iter()
+            .map(|include| config_folder.join(include))
+            .collect();
+        let config_dir = config_folder.join(CONFIG_DIR);
+        if config_dir.is_dir() {
+            let mut config_dir_files: Vec<PathBuf> = fs::read_dir(&config_dir)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| {
+                    path.is_file()
+                        && matches!(
+                            path.extension().and_then(|e| e.to_str()),
+                            Some("yml") | Some("yaml")
+                        )
+                })
+                .collect();
+            config_dir_files.sort();
+            files.extend(config_dir_files);
+        }
+        Ok(files)
+    }
+
+    /// merges the environments of the included files, see `KafkyEnvironment::merge`
+    pub(super) fn merge_included(&mut self) -> Result<(), KafkyError> {
+        for file in self.included_files()? {
+            debug!("including configuration {:?}", &file);
+            if !file.exists() {
+                return Err(KafkyError::ConfigurationNotFound(
+                    file.display().to_string(),
+                ));
+            }
+            let cfg = Self::read_source(&file)?;
+            let included: KafkyConfig = cfg.try_into()?;
+            if !included.include.is_empty() {
+                return Err(Self::include_error(
+                    &file,
+                    "nested includes are not supported",
+                ));
+            }
+            if included.default_environment.is_some() {
+                if self.default_environment.is_some()
+                    && self.default_environment != included.default_environment
+                {
+                    return Err(Self::include_error(
+                        &file,
+                        "defaultEnvironment is already set",
+                    ));
+                }
+                self.default_environment = included.default_environment;
+            }
+            for environment in included.environments {
+                match self
+                    .environments
+                    .iter_mut()
+                    .find(|e| e.name == environment.name)
+                {
+                    Some(existing) => existing
+                        .merge(environment)
+                        .map_err(|e| Self::include_error(&file, &e))?,
+                    None => self.environments.push(environment),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn include_error(file: &Path, message: &str) -> KafkyError {
+        KafkyError::InvalidConfiguration(format!("included file {}: {}", file.display(), message))
+    }
+}
+
+impl KafkyEnvironment {
+    /// environments defined in several files are merged: credentials and properties are added,
+    /// the other fields can be defined in one file only, or with the same value
+    fn merge(&mut self, other: KafkyEnvironment) -> Result<(), String> {
+        let conflict = |field: &str| {
+            format!(
+                "environment {} has a different {} in another file",
+                self.name, field
+            )
+        };
+        if !other.brokers.is_empty() {
+            if !self.brokers.is_empty() && self.brokers != other.brokers {
+                return Err(conflict("brokers"));
+            }
+            self.brokers = other.brokers;
+        }
+        if other.truststore.is_some() {
+            if self.truststore.is_some() {
+                return Err(conflict("truststore"));
+            }
+            self.truststore = other.truststore;
+        }
+        if other.security_protocol.is_some() {
+            if self.security_protocol.is_some() && self.security_protocol != other.security_protocol
+            {
+                return Err(conflict("securityProtocol"));
+            }
+            self.security_protocol = other.security_protocol;
+        }
+        if other.schema_registry.is_some() {
+            if self.schema_registry.is_some() && self.schema_registry != other.schema_registry {
+                return Err(conflict("schemaRegistry"));
+            }
+            self.schema_registry = other.schema_registry;
+        }
+        if other.default_credential.is_some() {
+            if self.default_credential.is_some()
+                && self.default_credential != other.default_credential
+            {
+                return Err(conflict("defaultCredential"));
+            }
+            self.default_credential = other.default_credential;
+        }
+        // any file can make the environment read-only or protected
+        self.read_only |= other.read_only;
+        self.protected |= other.protected;
+        for (key, value) in other.properties {
+            match self.properties.get(&key) {
+                Some(existing) if existing != &value => {
+                    return Err(conflict(&format!("value for the property {}", key)))
+                }
+                _ => {
+                    self.properties.insert(key, value);
+                }
+            }
+        }
+        for credential in other.credentials {
+            if self.get_credential(&credential.name).is_some() {
+                return Err(format!(
+                    "credential {} of the environment {} is defined in another file",
+                    credential.name, self.name
+                ));
+            }
+            self.credentials.push(credential);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn include_test() -> Result<(), KafkyError> {
+        let config_dir = tempfile::tempdir()?;
+        let config_file = config_dir.path().join("config.yml");
+        fs::write(
+            &config_file,
+            indoc! {"
+                include:
+                  - team.yml
+                environments:
+                  - name: dev
+                    brokers:
+                      - localhost:9092
+                    credentials:
+                      - name: admin
+                        plain:
+                          username: admin
+                          password: admin
+            "},
+        )?;
+        fs::write(
+            config_dir.path().join("team.yml"),
+            indoc! {"
+                environments:
+                  - name: prod
+                    brokers:
+                      - kafka-1:9093
+                    securityProtocol: SASL_SSL
+            "},
+        )?;
+        fs::create_dir(config_dir.path().join(CONFIG_DIR))?;
+        fs::write(
+            config_dir.path().join(CONFIG_DIR).join("personal.yml"),
+            indoc! {"
+                environments:
+                  - name: prod
+                    credentials:
+                      - name: me
+                        scram:
+                          username: me
+                          password:
+                            env: KAFKA_PASSWORD
+            "},
+        )?;
+
+        let config = KafkyConfig::load(&config_file)?;
+        assert_eq!(config.get_environment_names(), vec!["dev", "prod"]);
+        let prod = config.get_environment("prod").unwrap();
+        assert_eq!(prod.brokers, vec!["kafka-1:9093"]);
+        assert_eq!(prod.get_credential_names(), vec!["me"]);
+        assert!(config.validate().is_empty());
+        assert_eq!(
+            KafkyConfig::load_file(&config_file)?.get_environment_names(),
+            vec!["dev"]
+        );
+
+        fs::write(
+            config_dir.path().join(CONFIG_DIR).join("other.yml"),
+            indoc! {"
+                environments:
+                  - name: prod
+                    brokers:
+                      - kafka-2:9093
+            "},
+        )?;
+        match KafkyConfig::load(&config_file) {
+            Err(KafkyError::InvalidConfiguration(message)) => assert!(
+                message.contains("environment prod has a different brokers"),
+                "{}",
+                message
+            ),
+            _ => panic!("conflicting brokers error expected"),
+        }
+        Ok(())
+    }
+}
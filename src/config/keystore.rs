@@ -0,0 +1,515 @@
+use std::convert::TryInto;
+use std::fs;
+
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::{PKey, Private};
+use openssl::sha::sha1;
+use openssl::x509::X509;
+
+use crate::config::{KafkyKeystore, KafkyKeystoreFormat, KafkyKeystoreSource};
+
+const JKS_MAGIC: u32 = 0xFEED_FEED;
+const JCEKS_MAGIC: u32 = 0xCECE_CECE;
+const JKS_PRIVATE_KEY_TAG: u32 = 1;
+const JKS_TRUSTED_CERT_TAG: u32 = 2;
+const JKS_DIGEST_LEN: usize = 20;
+const JKS_INTEGRITY_SALT: &[u8] = b"Mighty Aphrodite";
+
+impl KafkyKeystoreSource {
+    /// configuration key of the source
+    pub fn key(&self) -> &'static str {
+        match self {
+            KafkyKeystoreSource::Path(_) => "path",
+            KafkyKeystoreSource::Base64(_) => "base64",
+        }
+    }
+
+    pub fn read(&self) -> Result<Vec<u8>, String> {
+        match self {
+            KafkyKeystoreSource::Path(path) => {
+                fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))
+            }
+            KafkyKeystoreSource::Base64(b64) => {
+                base64::decode(b64).map_err(|e| format!("invalid base64: {}", e))
+            }
+        }
+    }
+}
+
+impl KafkyKeystore {
+    pub fn load(
+        &self,
+        format: KafkyKeystoreFormat,
+        password: &str,
+        key_password: Option<&str>,
+    ) -> Result<KafkyKeystoreContent, String> {
+        let content = self.store.read()?;
+        match format {
+            KafkyKeystoreFormat::Pkcs12 => load_pkcs12(&content, password, self.alias.as_deref()),
+            KafkyKeystoreFormat::Jks => load_jks(
+                &content,
+                password,
+                key_password.unwrap_or(password),
+                self.alias.as_deref(),
+            ),
+        }
+    }
+}
+
+/// Certificate, private key and CA chain extracted from a PKCS#12 or JKS keystore
+#[derive(Default)]
+pub struct KafkyKeystoreContent {
+    pub certificate: Option<X509>,
+    pub private_key: Option<PKey<Private>>,
+    /// chain of the certificate
+    pub ca_chain: Vec<X509>,
+    /// trusted certificate entries of a JKS store
+    pub trusted: Vec<X509>,
+}
+
+impl KafkyKeystoreContent {
+    /// certificate followed by its CA chain
+    pub fn certificate_pem(&self) -> Result<Option<String>, String> {
+        match &self.certificate {
+            None => Ok(None),
+            Some(certificate) => {
+                let mut pem = to_pem(certificate)?;
+                pem.push_str(&self.ca_chain_pem()?);
+                Ok(Some(pem))
+            }
+        }
+    }
+
+    pub fn private_key_pem(&self) -> Result<Option<String>, String> {
+        self.private_key
+            .as_ref()
+            .map(|key| {
+                key.private_key_to_pem_pkcs8()
+                    .map_err(|e| e.to_string())
+                    .and_then(|pem| String::from_utf8(pem).map_err(|e| e.to_string()))
+            })
+            .transpose()
+    }
+
+    pub fn ca_chain_pem(&self) -> Result<String, String> {
+        let mut pem = String::new();
+        for ca in &self.ca_chain {
+            pem.push_str(&to_pem(ca)?);
+        }
+        Ok(pem)
+    }
+
+    /// every certificate of the store, used to build a truststore
+    pub fn all_certificates_pem(&self) -> Result<String, String> {
+        let mut pem = self.certificate_pem()?.unwrap_or_default();
+        if self.certificate.is_none() {
+            pem.push_str(&self.ca_chain_pem()?);
+        }
+        for trusted in &self.trusted {
+            pem.push_str(&to_pem(trusted)?);
+        }
+        Ok(pem)
+    }
+}
+
+fn to_pem(certificate: &X509) -> Result<String, String> {
+    certificate
+        .to_pem()
+        .map_err(|e| e.to_string())
+        .and_then(|pem| String::from_utf8(pem).map_err(|e| e.to_string()))
+}
+
+pub fn load_pkcs12(
+    content: &[u8],
+    password: &str,
+    alias: Option<&str>,
+) -> Result<KafkyKeystoreContent, String> {
+    let parsed = Pkcs12::from_der(content)
+        .and_then(|pkcs12| pkcs12.parse2(password))
+        .map_err(|e| format!("invalid PKCS#12 store or password: {}", e))?;
+    if let (Some(alias), Some(certificate)) = (alias, &parsed.cert) {
+        let cert_alias = certificate
+            .alias()
+            .map(|a| String::from_utf8_lossy(a).to_string());
+        if cert_alias.as_deref() != Some(alias) {
+            return Err(format!(
+                "alias {} not found, the store contains {}",
+                alias,
+                cert_alias.unwrap_or_else(|| "an unnamed entry".to_string())
+            ));
+        }
+    }
+    Ok(KafkyKeystoreContent {
+        certificate: parsed.cert,
+        private_key: parsed.pkey,
+        ca_chain: parsed
+            .ca
+            .map(|stack| stack.into_iter().collect())
+            .unwrap_or_default(),
+        trusted: vec![],
+    })
+}
+
+/// loads a Java KeyStore, only the JKS format (not JCEKS) is supported.
+/// Without alias the first private key entry is used, its chain is the CA chain; the trusted
+/// certificates are kept apart, all of them without alias
+pub fn load_jks(
+    content: &[u8],
+    store_password: &str,
+    key_password: &str,
+    alias: Option<&str>,
+) -> Result<KafkyKeystoreContent, String> {
+    if content.len() < JKS_DIGEST_LEN {
+        return Err("invalid JKS store".to_string());
+    }
+    let (data, digest) = content.split_at(content.len() - JKS_DIGEST_LEN);
+    let mut integrity_data = jks_password(store_password);
+    integrity_data.extend_from_slice(JKS_INTEGRITY_SALT);
+    integrity_data.extend_from_slice(data);
+    if sha1(&integrity_data) != digest {
+        return Err("invalid JKS store password or corrupted store".to_string());
+    }
+
+    let mut reader = JksReader { data, pos: 0 };
+    match reader.u32()? {
+        JKS_MAGIC => {}
+        JCEKS_MAGIC => return Err("JCEKS stores are not supported".to_string()),
+        _ => return Err("invalid JKS store".to_string()),
+    }
+    let version = reader.u32()?;
+    if version != 1 && version != 2 {
+        return Err(format!("unsupported JKS version {}", version));
+    }
+
+    let mut result = KafkyKeystoreContent::default();
+    let mut key_found = false;
+    for _ in 0..reader.u32()? {
+        let tag = reader.u32()?;
+        let entry_alias = reader.utf()?;
+        reader.bytes(8)?; // creation date
+        match tag {
+            JKS_PRIVATE_KEY_TAG => {
+                let encrypted_key = reader.sized_bytes()?;
+                let mut chain = Vec::new();
+                for _ in 0..reader.u32()? {
+                    chain.push(reader.certificate(version)?);
+                }
+                let selected = match alias {
+                    Some(alias) => alias == entry_alias,
+                    None => !key_found,
+                };
+                if selected && !key_found {
+                    key_found = true;
+                    let pkcs8 = jks_decrypt_key(encrypted_key, key_password)?;
+                    result.private_key =
+                        Some(PKey::private_key_from_pkcs8(&pkcs8).map_err(|e| e.to_string())?);
+                    let mut chain = chain.into_iter();
+                    result.certificate = chain.next();
+                    result.ca_chain.extend(chain);
+                }
+            }
+            JKS_TRUSTED_CERT_TAG => {
+                let certificate = reader.certificate(version)?;
+                if alias.is_none() || alias == Some(entry_alias.as_str()) {
+                    result.trusted.push(certificate);
+                }
+            }
+            _ => return Err(format!("invalid JKS entry type {}", tag)),
+        }
+    }
+    if let Some(alias) = alias {
+        if !key_found && result.trusted.is_empty() {
+            return Err(format!("alias {} not found", alias));
+        }
+    }
+    Ok(result)
+}
+
+/// JKS passwords are hashed as UTF-16 big endian
+fn jks_password(password: &str) -> Vec<u8> {
+    password
+        .encode_utf16()
+        .flat_map(|c| c.to_be_bytes())
+        .collect()
+}
+
+/// Sun proprietary key protection (OID 1.3.6.1.4.1.42.2.17.1.1):
+/// salt(20) | key XOR sha1 key stream | sha1(password | key)
+fn jks_decrypt_key(encrypted_key_info: &[u8], password: &str) -> Result<Vec<u8>, String> {
+    let protected_key = der_encrypted_data(encrypted_key_info)?;
+    if protected_key.len() < 2 * JKS_DIGEST_LEN {
+        return Err("invalid JKS private key".to_string());
+    }
+    let password = jks_password(password);
+    let salt = &protected_key[..JKS_DIGEST_LEN];
+    let encrypted = &protected_key[JKS_DIGEST_LEN..protected_key.len() - JKS_DIGEST_LEN];
+    let check = &protected_key[protected_key.len() - JKS_DIGEST_LEN..];
+
+    let mut key = Vec::with_capacity(encrypted.len());
+    let mut digest: Vec<u8> = salt.to_vec();
+    for chunk in encrypted.chunks(JKS_DIGEST_LEN) {
+        digest = sha1(&[password.as_slice(), &digest].concat()).to_vec();
+        key.extend(chunk.iter().zip(digest.iter()).map(|(e, d)| e ^ d));
+    }
+    if sha1(&[password.as_slice(), &key].concat()) != check {
+        return Err("invalid JKS private key password".to_string());
+    }
+    Ok(key)
+}
+
+/// encrypted data of a DER EncryptedPrivateKeyInfo: SEQUENCE { AlgorithmIdentifier, OCTET STRING }
+fn der_encrypted_data(der: &[u8]) -> Result<&[u8], String> {
+    let (tag, content, _) = der_element(der)?;
+    if tag != 0x30 {
+        return Err("invalid JKS private key".to_string());
+    }
+    let (_, _, rest) = der_element(content)?;
+    let (tag, encrypted, _) = der_element(rest)?;
+    if tag != 0x04 {
+        return Err("invalid JKS private key".to_string());
+    }
+    Ok(encrypted)
+}
+
+/// (tag, content, remaining bytes) of the first DER element
+fn der_element(der: &[u8]) -> Result<(u8, &[u8], &[u8]), String> {
+    let invalid = || "invalid DER content".to_string();
+    let tag = *der.first().ok_or_else(invalid)?;
+    let first_len = *der.get(1).ok_or_else(invalid)? as usize;
+    let (len, header_len) = if first_len < 0x80 {
+        (first_len, 2)
+    } else {
+        let len_bytes = first_len & 0x7f;
+        let len = der
+            .get(2..2 + len_bytes)
+            .ok_or_else(invalid)?
+            .iter()
+            .try_fold(0usize, |len, b| {
+                len.checked_mul(0x100).map(|len| len | *b as usize)
+            })
+            .ok_or_else(invalid)?;
+        (len, 2 + len_bytes)
+    };
+    let end = header_len.checked_add(len).ok_or_else(invalid)?;
+    let content = der.get(header_len..end).ok_or_else(invalid)?;
+    Ok((tag, content, &der[end..]))
+}
+
+struct JksReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> JksReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| "truncated JKS store".to_string())?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn utf(&mut self) -> Result<String, String> {
+        let len = u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()) as usize;
+        Ok(String::from_utf8_lossy(self.bytes(len)?).to_string())
+    }
+
+    fn sized_bytes(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    fn certificate(&mut self, version: u32) -> Result<X509, String> {
+        if version == 2 {
+            let cert_type = self.utf()?;
+            if cert_type != "X.509" {
+                return Err(format!("unsupported certificate type {}", cert_type));
+            }
+        }
+        X509::from_der(self.sized_bytes()?).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::hash::MessageDigest;
+    use openssl::rsa::Rsa;
+    use openssl::x509::X509NameBuilder;
+
+    pub fn self_signed(cn: &str) -> (X509, PKey<Private>) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", cn).unwrap();
+        let name = name.build();
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder
+            .set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap())
+            .unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(10).unwrap())
+            .unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        (builder.build(), key)
+    }
+
+    fn der_header(tag: u8, len: usize) -> Vec<u8> {
+        let mut header = vec![tag];
+        if len < 0x80 {
+            header.push(len as u8);
+        } else {
+            header.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]);
+        }
+        header
+    }
+
+    fn jks_utf(value: &str) -> Vec<u8> {
+        [&(value.len() as u16).to_be_bytes()[..], value.as_bytes()].concat()
+    }
+
+    /// JKS v2 store with a private key entry and a trusted certificate entry
+    pub fn jks_store(
+        password: &str,
+        alias: &str,
+        certificate: &X509,
+        key: &PKey<Private>,
+        trusted: &X509,
+    ) -> Vec<u8> {
+        let jks_pwd = jks_password(password);
+        let pkcs8 = key.private_key_to_pkcs8().unwrap();
+        let salt = [7u8; JKS_DIGEST_LEN];
+        let mut protected_key = salt.to_vec();
+        let mut digest = salt.to_vec();
+        for chunk in pkcs8.chunks(JKS_DIGEST_LEN) {
+            digest = sha1(&[jks_pwd.as_slice(), &digest].concat()).to_vec();
+            protected_key.extend(chunk.iter().zip(digest.iter()).map(|(k, d)| k ^ d));
+        }
+        protected_key.extend_from_slice(&sha1(&[jks_pwd.as_slice(), &pkcs8].concat()));
+
+        // AlgorithmIdentifier { 1.3.6.1.4.1.42.2.17.1.1, NULL }
+        let algorithm = [
+            0x30, 0x0e, 0x06, 0x0a, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x2a, 0x02, 0x11, 0x01, 0x01,
+            0x05, 0x00,
+        ];
+        let octet_string = [der_header(0x04, protected_key.len()), protected_key].concat();
+        let body = [&algorithm[..], &octet_string].concat();
+        let encrypted_key_info = [der_header(0x30, body.len()), body].concat();
+
+        let cert_der = certificate.to_der().unwrap();
+        let trusted_der = trusted.to_der().unwrap();
+        let mut store = Vec::new();
+        store.extend_from_slice(&JKS_MAGIC.to_be_bytes());
+        store.extend_from_slice(&2u32.to_be_bytes());
+        store.extend_from_slice(&2u32.to_be_bytes());
+        store.extend_from_slice(&JKS_PRIVATE_KEY_TAG.to_be_bytes());
+        store.extend(jks_utf(alias));
+        store.extend_from_slice(&0u64.to_be_bytes());
+        store.extend_from_slice(&(encrypted_key_info.len() as u32).to_be_bytes());
+        store.extend(encrypted_key_info);
+        store.extend_from_slice(&1u32.to_be_bytes());
+        store.extend(jks_utf("X.509"));
+        store.extend_from_slice(&(cert_der.len() as u32).to_be_bytes());
+        store.extend(cert_der);
+        store.extend_from_slice(&JKS_TRUSTED_CERT_TAG.to_be_bytes());
+        store.extend(jks_utf("ca"));
+        store.extend_from_slice(&0u64.to_be_bytes());
+        store.extend(jks_utf("X.509"));
+        store.extend_from_slice(&(trusted_der.len() as u32).to_be_bytes());
+        store.extend(trusted_der);
+        let digest = sha1(&[jks_pwd.as_slice(), JKS_INTEGRITY_SALT, &store].concat());
+        store.extend_from_slice(&digest);
+        store
+    }
+
+    #[test]
+    fn load_pkcs12_test() {
+        let (certificate, key) = self_signed("kafky-client");
+        let (ca, _) = self_signed("kafky-ca");
+        let mut ca_stack = openssl::stack::Stack::new().unwrap();
+        ca_stack.push(ca.clone()).unwrap();
+        let mut builder = Pkcs12::builder();
+        builder
+            .name("client")
+            .pkey(&key)
+            .cert(&certificate)
+            .ca(ca_stack);
+        let der = builder.build2("store-pwd").unwrap().to_der().unwrap();
+
+        let content = load_pkcs12(&der, "store-pwd", Some("client")).unwrap();
+        assert!(content.private_key.unwrap().public_eq(key.as_ref()));
+        assert_eq!(
+            content.certificate.unwrap().to_der().unwrap(),
+            certificate.to_der().unwrap()
+        );
+        assert_eq!(content.ca_chain.len(), 1);
+
+        assert!(load_pkcs12(&der, "wrong", None).is_err());
+        assert!(load_pkcs12(&der, "store-pwd", Some("other")).is_err());
+    }
+
+    #[test]
+    fn load_jks_test() {
+        let (certificate, key) = self_signed("kafky-client");
+        let (ca, _) = self_signed("kafky-ca");
+        let store = jks_store("store-pwd", "client", &certificate, &key, &ca);
+
+        let content = load_jks(&store, "store-pwd", "store-pwd", None).unwrap();
+        assert!(content
+            .private_key
+            .as_ref()
+            .unwrap()
+            .public_eq(key.as_ref()));
+        assert_eq!(
+            content.certificate.as_ref().unwrap().to_der().unwrap(),
+            certificate.to_der().unwrap()
+        );
+        // the trusted certificates aren't part of the chain of the key entry
+        assert!(content.ca_chain.is_empty());
+        assert_eq!(content.trusted.len(), 1);
+        assert_eq!(
+            content
+                .certificate_pem()
+                .unwrap()
+                .unwrap()
+                .matches("BEGIN CERTIFICATE")
+                .count(),
+            1
+        );
+        assert_eq!(
+            content
+                .all_certificates_pem()
+                .unwrap()
+                .matches("BEGIN CERTIFICATE")
+                .count(),
+            2
+        );
+
+        let trusted_only = load_jks(&store, "store-pwd", "store-pwd", Some("ca")).unwrap();
+        assert!(trusted_only.private_key.is_none());
+        assert_eq!(trusted_only.trusted.len(), 1);
+
+        assert!(load_jks(&store, "wrong", "store-pwd", None).is_err());
+        assert!(load_jks(&store, "store-pwd", "wrong", None).is_err());
+        assert!(load_jks(&store, "store-pwd", "store-pwd", Some("missing")).is_err());
+
+        // lengths overflowing usize
+        assert!(
+            der_element(&[0x04, 0x88, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err()
+        );
+        assert!(der_element(&[0x04, 0x89, 1, 0, 0, 0, 0, 0, 0, 0, 0]).is_err());
+    }
+}
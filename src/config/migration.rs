@@ -0,0 +1,287 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use config::{Config, File, FileFormat};
+use log::debug;
+use serde_yaml::{Mapping, Value};
+
+use crate::config::KafkyConfig;
+use crate::errors::KafkyError;
+
+type KafkyMigrationStep = fn(&mut Mapping) -> Result<(), String>;
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` layout to the version `n + 2`
+const MIGRATIONS: [KafkyMigrationStep; 1] = [v1_to_v2];
+
+/// version of the layout read by this kafky, written in the new configuration files
+pub const CONFIG_VERSION: u64 = MIGRATIONS.len() as u64 + 1;
+
+/// the files without `version` are version 1, version 2 only adds the field
+fn v1_to_v2(_config: &mut Mapping) -> Result<(), String> {
+    Ok(())
+}
+
+/// an older configuration file upgraded to the current version
+pub struct KafkyConfigMigration {
+    pub file: PathBuf,
+    pub from: u64,
+    pub to: u64,
+    pub original: String,
+    pub migrated: String,
+    /// whether a step changed more than the version
+    pub changes_layout: bool,
+}
+
+impl KafkyConfigMigration {
+    /// the migration needed by the file content, `None` if it's already at the current version
+    pub fn of(config_file: &Path, content: &str) -> Result<Option<Self>, KafkyError> {
+        Self::with_steps(config_file, content, &MIGRATIONS)
+    }
+
+    /// applies the steps from the version of the file, the last one gives the target version
+    fn with_steps(
+        config_file: &Path,
+        content: &str,
+        steps: &[KafkyMigrationStep],
+    ) -> Result<Option<Self>, KafkyError> {
+        let migration_error = |message: String| {
+            KafkyError::InvalidConfiguration(format!("{}: {}", config_file.display(), message))
+        };
+        let target_version = steps.len() as u64 + 1;
+        let mut config = match serde_yaml::from_str::<Value>(content) {
+            Ok(Value::Mapping(config)) => config,
+            // the loader reports the syntax errors
+            _ => return Ok(None),
+        };
+        let version = match config.remove(&Value::from("version")) {
+            None => 1,
+            Some(version) => version
+                .as_u64()
+                .filter(|v| *v > 0)
+                .ok_or_else(|| migration_error("invalid version".to_string()))?,
+        };
+        if version > target_version {
+            return Err(KafkyError::UnsupportedConfigVersion(
+                config_file.display().to_string(),
+                version,
+                target_version,
+            ));
+        }
+        if version == target_version {
+            return Ok(None);
+        }
+        let original_config = config.clone();
+        for step in &steps[(version - 1) as usize..] {
+            step(&mut config).map_err(migration_error)?;
+        }
+        let changes_layout = config != original_config;
+        let mut migrated = Mapping::new();
+        migrated.insert(Value::from("version"), Value::from(target_version));
+        migrated.extend(config);
+        Ok(Some(KafkyConfigMigration {
+            file: config_file.to_path_buf(),
+            from: version,
+            to: target_version,
+            original: content.to_string(),
+            migrated: serde_yaml::to_string(&migrated)
+                .map_err(|e| migration_error(e.to_string()))?,
+            changes_layout,
+        }))
+    }
+
+    /// the original file is kept as `<file>.v<version>.bak`
+    pub fn backup_path(&self) -> PathBuf {
+        let mut backup = self.file.as_os_str().to_os_string();
+        backup.push(format!(".v{}.bak", self.from));
+        PathBuf::from(backup)
+    }
+
+    /// the unified diff between the original and the migrated file
+    pub fn diff(&self) -> String {
+        similar::TextDiff::from_lines(&self.original, &self.migrated)
+            .unified_diff()
+            .header(
+                &self.file.display().to_string(),
+                &format!("{} (version {})", self.file.display(), self.to),
+            )
+            .to_string()
+    }
+
+    /// writes the migrated file after the backup of the original, the only one with the comments
+    fn apply(&self) -> Result<(), KafkyError> {
+        fs::copy(&self.file, self.backup_path())?;
+        KafkyConfig::write_file(&self.file, &self.migrated)
+    }
+}
+
+impl<'a> KafkyConfig<'a> {
+    /// reads a configuration file, older versions are migrated in memory
+    pub(super) fn read_source(config_file: &Path) -> Result<Config, KafkyError> {
+        let mut cfg = Config::default();
+        match KafkyConfigMigration::of(config_file, &fs::read_to_string(config_file)?)? {
+            Some(migration) => {
+                // a file only missing the version is read as is, no need to bother
+                if migration.changes_layout {
+                    eprintln!(
+                        "warning: {} has the configuration version {}, run `kafky config migrate` to upgrade it",
+                        config_file.display(),
+                        migration.from
+                    );
+                } else {
+                    debug!(
+                        "{} has the configuration version {}",
+                        config_file.display(),
+                        migration.from
+                    );
+                }
+                cfg.merge(File::from_str(&migration.migrated, FileFormat::Yaml))?;
+            }
+            None => {
+                cfg.merge(File::from(config_file))?;
+            }
+        }
+        Ok(cfg)
+    }
+
+    /// the migrations of the configuration file and of its included files
+    pub fn migrations(config_file: &Path) -> Result<Vec<KafkyConfigMigration>, KafkyError> {
+        let mut files = vec![config_file.to_path_buf()];
+        files.extend(
+            KafkyConfig::load_file(config_file)?
+                .included_files()?
+                .into_iter()
+                .filter(|file| file.exists()),
+        );
+        let mut migrations = vec![];
+        for file in files {
+            if let Some(migration) = KafkyConfigMigration::of(&file, &fs::read_to_string(&file)?)? {
+                migrations.push(migration);
+            }
+        }
+        Ok(migrations)
+    }
+
+    /// rewrites the older configuration files at the current version, returns the applied migrations
+    pub fn migrate(config_file: &Path) -> Result<Vec<KafkyConfigMigration>, KafkyError> {
+        let migrations = Self::migrations(config_file)?;
+        for migration in &migrations {
+            migration.apply()?;
+        }
+        Ok(migrations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    const LEGACY_ENVIRONMENT: &str = indoc! {"
+        # legacy layout
+        environments:
+          - name: dev
+            brokers:
+              - localhost:9092
+            credentials: []
+    "};
+
+    #[test]
+    fn migrate_test() -> Result<(), KafkyError> {
+        let config_dir = tempfile::tempdir()?;
+        let config_file = config_dir.path().join("config.yml");
+        fs::write(
+            &config_file,
+            format!("include:\n  - team.yml\n{}", LEGACY_ENVIRONMENT),
+        )?;
+        fs::write(
+            config_dir.path().join("team.yml"),
+            LEGACY_ENVIRONMENT.replace("dev", "team"),
+        )?;
+        fs::create_dir(config_dir.path().join("config.d"))?;
+        let shared_file = config_dir.path().join("config.d").join("shared.yml");
+        fs::write(&shared_file, LEGACY_ENVIRONMENT.replace("dev", "shared"))?;
+
+        // the included files are migrated with the main one
+        let migrations = KafkyConfig::migrations(&config_file)?;
+        assert_eq!(migrations.len(), 3);
+        assert!(migrations
+            .iter()
+            .all(|m| m.from == 1 && m.to == CONFIG_VERSION && m.diff().contains("+version: 2")));
+        // only the version is added, the files are read without warning
+        assert!(migrations.iter().all(|m| !m.changes_layout));
+        let config = KafkyConfig::load(&config_file)?;
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(
+            config.get_environment_names(),
+            vec!["dev", "team", "shared"]
+        );
+
+        assert_eq!(KafkyConfig::migrate(&config_file)?.len(), 3);
+        assert!(
+            fs::read_to_string(config_dir.path().join("config.yml.v1.bak"))?
+                .starts_with("include:")
+        );
+        assert!(
+            fs::read_to_string(config_dir.path().join("config.d").join("shared.yml.v1.bak"))?
+                .starts_with("# legacy layout")
+        );
+        assert!(fs::read_to_string(&config_file)?.starts_with("---\nversion: 2\n"));
+        assert!(fs::read_to_string(&shared_file)?.starts_with("---\nversion: 2\n"));
+        assert!(KafkyConfig::migrate(&config_file)?.is_empty());
+
+        fs::write(&config_file, "version: 99\nenvironments: []\n")?;
+        assert!(matches!(
+            KafkyConfig::load(&config_file),
+            Err(KafkyError::UnsupportedConfigVersion(_, 99, CONFIG_VERSION))
+        ));
+        Ok(())
+    }
+
+    /// a version 3 turning the comma separated brokers into lists
+    fn split_brokers(config: &mut Mapping) -> Result<(), String> {
+        let environments = config
+            .get_mut(&Value::from("environments"))
+            .and_then(Value::as_sequence_mut)
+            .ok_or("environments expected")?;
+        for environment in environments {
+            if let Some(brokers) = environment.get_mut("brokers") {
+                if let Some(list) = brokers.as_str() {
+                    *brokers = Value::Sequence(list.split(',').map(Value::from).collect());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn migration_steps_test() -> Result<(), KafkyError> {
+        let config_file = Path::new("config.yml");
+        let steps: [KafkyMigrationStep; 2] = [v1_to_v2, split_brokers];
+        let legacy = LEGACY_ENVIRONMENT.replace("\n      - localhost:9092", " a:9092,b:9092");
+
+        let migration = KafkyConfigMigration::with_steps(config_file, &legacy, &steps)?
+            .expect("migration expected");
+        assert_eq!((migration.from, migration.to), (1, 3));
+        assert!(migration.changes_layout);
+        let migrated: Value = serde_yaml::from_str(&migration.migrated).unwrap();
+        assert_eq!(migrated["version"], Value::from(3));
+        assert_eq!(
+            migrated["environments"][0]["brokers"],
+            Value::Sequence(vec![Value::from("a:9092"), Value::from("b:9092")])
+        );
+        let diff = migration.diff();
+        assert!(diff.contains("-    brokers: a:9092,b:9092"), "{}", diff);
+        assert!(diff.contains("+version: 3"));
+
+        // a version 2 file goes through the last step only
+        let v2 = format!("version: 2\n{}", legacy);
+        let migration = KafkyConfigMigration::with_steps(config_file, &v2, &steps)?
+            .expect("migration expected");
+        assert_eq!(migration.from, 2);
+        assert!(migration.changes_layout);
+        assert!(
+            KafkyConfigMigration::with_steps(config_file, &migration.migrated, &steps)?.is_none()
+        );
+        Ok(())
+    }
+}
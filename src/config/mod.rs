@@ -0,0 +1,758 @@
+pub mod certificate;
+mod include;
+pub mod keystore;
+pub mod migration;
+pub mod properties;
+mod secret;
+pub mod validation;
+pub mod vault;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::create_dir;
+
+use std::io::{stdin, stdout, Write};
+use std::path::{Path, PathBuf};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::errors::KafkyError;
+pub use secret::{KafkySecret, KafkySecretReference, KafkySecretResolver};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KafkyPrivateKey {
+    #[serde(flatten)]
+    pub key: KafkyPEM,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<KafkySecret>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KafkyPEM {
+    Path(String),
+    Base64(String),
+    Pem(String),
+    Pkcs12(KafkyKeystore),
+    Jks(KafkyKeystore),
+}
+
+impl KafkyPEM {
+    pub fn keystore(&self) -> Option<(KafkyKeystoreFormat, &KafkyKeystore)> {
+        match self {
+            KafkyPEM::Pkcs12(keystore) => Some((KafkyKeystoreFormat::Pkcs12, keystore)),
+            KafkyPEM::Jks(keystore) => Some((KafkyKeystoreFormat::Jks, keystore)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum KafkyKeystoreFormat {
+    Pkcs12,
+    Jks,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KafkyKeystoreSource {
+    Path(String),
+    Base64(String),
+}
+
+/// PKCS#12 or JKS store, the key password defaults to the store one
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KafkyKeystore {
+    #[serde(flatten)]
+    pub store: KafkyKeystoreSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<KafkySecret>,
+    #[serde(rename = "keyPassword", skip_serializing_if = "Option::is_none")]
+    pub key_password: Option<KafkySecret>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KafkySSLCredential {
+    pub certificate: KafkyPEM,
+    /// not needed when the certificate is a keystore containing the private key
+    #[serde(rename = "privateKey", skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<KafkyPrivateKey>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KafkyPlainCredential {
+    pub username: String,
+    pub password: KafkySecret,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+pub enum KafkyScramMechanism {
+    #[serde(rename = "SCRAM-SHA-256")]
+    ScramSha256,
+    #[serde(rename = "SCRAM-SHA-512")]
+    #[default]
+    ScramSha512,
+}
+
+impl KafkyScramMechanism {
+    pub fn sasl_mechanism(&self) -> &'static str {
+        match self {
+            KafkyScramMechanism::ScramSha256 => "SCRAM-SHA-256",
+            KafkyScramMechanism::ScramSha512 => "SCRAM-SHA-512",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KafkyScramCredential {
+    #[serde(default)]
+    pub mechanism: KafkyScramMechanism,
+    pub username: String,
+    pub password: KafkySecret,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KafkyOAuthBearerCredential {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    #[serde(rename = "clientSecret")]
+    pub client_secret: KafkySecret,
+    #[serde(rename = "tokenEndpointUrl")]
+    pub token_endpoint_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// SASL extensions sent with the token, e.g. `logicalCluster`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extensions: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KafkyCredentialKind {
+    Ssl(KafkySSLCredential),
+    Plain(KafkyPlainCredential),
+    Scram(KafkyScramCredential),
+    OAuthBearer(KafkyOAuthBearerCredential),
+}
+
+impl KafkyCredentialKind {
+    /// configuration key of the credential kind
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            KafkyCredentialKind::Ssl(_) => "ssl",
+            KafkyCredentialKind::Plain(_) => "plain",
+            KafkyCredentialKind::Scram(_) => "scram",
+            KafkyCredentialKind::OAuthBearer(_) => "oauthbearer",
+        }
+    }
+
+    pub fn is_sasl(&self) -> bool {
+        match self {
+            KafkyCredentialKind::Ssl(_) => false,
+            KafkyCredentialKind::Plain(_)
+            | KafkyCredentialKind::Scram(_)
+            | KafkyCredentialKind::OAuthBearer(_) => true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy, Display)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+pub enum KafkySecurityProtocol {
+    Plaintext,
+    Ssl,
+    SaslPlaintext,
+    SaslSsl,
+}
+
+impl KafkySecurityProtocol {
+    pub fn is_sasl(&self) -> bool {
+        matches!(
+            self,
+            KafkySecurityProtocol::SaslPlaintext | KafkySecurityProtocol::SaslSsl
+        )
+    }
+
+    pub fn is_ssl(&self) -> bool {
+        matches!(
+            self,
+            KafkySecurityProtocol::Ssl | KafkySecurityProtocol::SaslSsl
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KafkyCredential {
+    pub name: String,
+    #[serde(flatten)]
+    pub credential: KafkyCredentialKind,
+    /// librdkafka properties overriding the environment ones
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KafkyEnvironment {
+    pub name: String,
+    #[serde(default)]
+    pub brokers: Vec<String>,
+    #[serde(default)]
+    pub credentials: Vec<KafkyCredential>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truststore: Option<KafkyPEM>,
+    #[serde(rename = "securityProtocol", skip_serializing_if = "Option::is_none")]
+    pub security_protocol: Option<KafkySecurityProtocol>,
+    /// librdkafka properties applied on top of the kafky ones
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, String>,
+    /// credential used when none is given with -c or KAFKY_CREDENTIAL
+    #[serde(rename = "defaultCredential", skip_serializing_if = "Option::is_none")]
+    pub default_credential: Option<String>,
+    /// blocks the commands writing to the cluster
+    #[serde(rename = "readOnly", default, skip_serializing_if = "is_false")]
+    pub read_only: bool,
+    /// the environment name must be typed to confirm destructive commands
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub protected: bool,
+    #[serde(rename = "schemaRegistry", skip_serializing_if = "Option::is_none")]
+    pub schema_registry: Option<KafkySchemaRegistry>,
+}
+
+/// Confluent compatible schema registry, used by the avro, protobuf and json formats
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct KafkySchemaRegistry {
+    pub url: String,
+    /// basic authentication
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<KafkySecret>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl KafkyEnvironment {
+    pub fn get_credential(&self, credential: &str) -> Option<&KafkyCredential> {
+        self.credentials.iter().find(|c| c.name.eq(credential))
+    }
+
+    pub fn get_credential_names(&self) -> Vec<String> {
+        self.credentials.iter().map(|c| c.name.clone()).collect()
+    }
+
+    pub fn check_writable(&self, action: &str) -> Result<(), KafkyError> {
+        if self.read_only {
+            return Err(KafkyError::ReadOnlyEnvironment(
+                self.name.clone(),
+                action.to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn add_credential(&mut self, credential: KafkyCredential) -> Result<(), KafkyError> {
+        if self.get_credential(&credential.name).is_some() {
+            return Err(KafkyError::CredentialAlreadyExists(
+                credential.name,
+                self.name.clone(),
+            ));
+        }
+        self.credentials.push(credential);
+        Ok(())
+    }
+
+    pub fn remove_credential(&mut self, credential: &str) -> Result<KafkyCredential, KafkyError> {
+        match self.credentials.iter().position(|c| c.name.eq(credential)) {
+            Some(idx) => Ok(self.credentials.remove(idx)),
+            None => Err(KafkyError::CredentialNotFound(
+                credential.to_string(),
+                self.name.clone(),
+                self.get_credential_names().join(","),
+            )),
+        }
+    }
+
+    /// security protocol to use with the credential: the `securityProtocol` override if present,
+    /// otherwise derived from the credential kind and the truststore presence
+    pub fn security_protocol(
+        &self,
+        credential: &KafkyCredential,
+    ) -> Result<KafkySecurityProtocol, KafkyError> {
+        let sasl = credential.credential.is_sasl();
+        match self.security_protocol {
+            None if !sasl => Ok(KafkySecurityProtocol::Ssl),
+            None if self.truststore.is_some() => Ok(KafkySecurityProtocol::SaslSsl),
+            None => Ok(KafkySecurityProtocol::SaslPlaintext),
+            Some(protocol) if protocol.is_sasl() == sasl && (sasl || protocol.is_ssl()) => {
+                Ok(protocol)
+            }
+            Some(protocol) => Err(KafkyError::InvalidSecurityProtocol(
+                protocol.to_string(),
+                self.name.clone(),
+                credential.name.clone(),
+            )),
+        }
+    }
+}
+
+fn empty_path<'a>() -> &'a Path {
+    Path::new("")
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KafkyConfig<'a> {
+    #[serde(skip)]
+    #[serde(default = "empty_path")]
+    path: &'a Path,
+    /// layout version, older files are migrated when loaded
+    #[serde(default)]
+    pub version: u64,
+    /// files merged into this one, relative to the config folder
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// environment used when none is given with -e or KAFKY_ENVIRONMENT
+    #[serde(rename = "defaultEnvironment", skip_serializing_if = "Option::is_none")]
+    pub default_environment: Option<String>,
+    pub environments: Vec<KafkyEnvironment>,
+}
+
+impl<'a> KafkyConfig<'a> {
+    /// reads the configuration merging the included files, without validating it:
+    /// commands check the environment they connect to, `config validate` the whole configuration
+    pub fn load(config_file: &'a Path) -> Result<Self, KafkyError> {
+        let mut config = Self::load_file(config_file)?;
+        config.merge_included()?;
+        Ok(config)
+    }
+
+    /// reads only the given file, without the included ones
+    pub fn load_file(config_file: &'a Path) -> Result<Self, KafkyError> {
+        debug!("checking configuration file presence {:?}", config_file);
+        if !config_file.exists() {
+            return Err(KafkyError::ConfigurationNotFound(
+                config_file.as_os_str().to_str().unwrap().to_string(),
+            ));
+        }
+        debug!("loading configuration {:?}", &config_file);
+        let cfg = Self::read_source(config_file)?;
+
+        cfg.try_into::<KafkyConfig>()
+            .map(|mut kafky_cfg| {
+                kafky_cfg.path = config_file;
+                kafky_cfg
+            })
+            .map_err(|e| e.into())
+    }
+
+    pub fn get_environment(&self, environment: &str) -> Option<&KafkyEnvironment> {
+        self.environments.iter().find(|e| e.name.eq(environment))
+    }
+
+    pub fn get_environment_names(&self) -> Vec<&str> {
+        self.environments.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    pub fn get_environment_mut(
+        &mut self,
+        environment: &str,
+    ) -> Result<&mut KafkyEnvironment, KafkyError> {
+        let names = self.get_environment_names().join(",");
+        self.environments
+            .iter_mut()
+            .find(|e| e.name.eq(environment))
+            .ok_or_else(|| KafkyError::EnvironmentNotFound(environment.to_string(), names))
+    }
+
+    pub fn add_environment(&mut self, environment: KafkyEnvironment) -> Result<(), KafkyError> {
+        if self.get_environment(&environment.name).is_some() {
+            return Err(KafkyError::EnvironmentAlreadyExists(environment.name));
+        }
+        self.environments.push(environment);
+        Ok(())
+    }
+
+    pub fn remove_environment(
+        &mut self,
+        environment: &str,
+    ) -> Result<KafkyEnvironment, KafkyError> {
+        match self
+            .environments
+            .iter()
+            .position(|e| e.name.eq(environment))
+        {
+            Some(idx) => Ok(self.environments.remove(idx)),
+            None => Err(KafkyError::EnvironmentNotFound(
+                environment.to_string(),
+                self.get_environment_names().join(","),
+            )),
+        }
+    }
+
+    /// writes the configuration back to its file, replacing it atomically
+    pub fn save(&self) -> Result<(), KafkyError> {
+        let yaml = serde_yaml::to_string(self)
+            .map_err(|e| KafkyError::InvalidConfiguration(e.to_string()))?;
+        debug!("saving configuration {:?}", self.path);
+        Self::write_file(self.path, &yaml)
+    }
+
+    /// replaces the file through a temporary one, keeping its permissions
+    pub(super) fn write_file(config_file: &Path, content: &str) -> Result<(), KafkyError> {
+        let mut tmp_file = tempfile::NamedTempFile::new_in(
+            config_file
+                .parent()
+                .expect("error getting parent config path"),
+        )?;
+        tmp_file.write_all(content.as_bytes())?;
+        if let Ok(metadata) = fs::metadata(config_file) {
+            fs::set_permissions(tmp_file.path(), metadata.permissions())?;
+        }
+        tmp_file
+            .persist(config_file)
+            .map_err(|e| KafkyError::from(e.error))?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &'a Path {
+        self.path
+    }
+
+    pub fn config_folder(&self) -> &'a Path {
+        self.path
+            .parent()
+            .expect("error getting parent config path")
+    }
+
+    pub fn vault_path(&self) -> PathBuf {
+        Self::vault_path_of(self.path)
+    }
+
+    pub fn vault_path_of(config_file: &Path) -> PathBuf {
+        config_file
+            .parent()
+            .expect("error getting parent config path")
+            .join("secrets.enc")
+    }
+
+    fn create_sample(config_file_path: &Path) -> Result<(), KafkyError> {
+        let env = KafkyEnvironment {
+            name: "sample-env".to_string(),
+            brokers: vec!["localhost:9094".to_string()],
+            truststore: Some(KafkyPEM::Path("truststore.pem".to_string())),
+            security_protocol: None,
+            properties: BTreeMap::new(),
+            default_credential: None,
+            read_only: false,
+            protected: false,
+            schema_registry: None,
+            credentials: vec![
+                KafkyCredential {
+                    name: "plain-cred".to_string(),
+                    credential: KafkyCredentialKind::Plain(KafkyPlainCredential {
+                        username: "kafka-user".to_string(),
+                        password: KafkySecret::from("kafka-password"),
+                    }),
+                    properties: BTreeMap::new(),
+                },
+                KafkyCredential {
+                    name: "scram-cred".to_string(),
+                    credential: KafkyCredentialKind::Scram(KafkyScramCredential {
+                        mechanism: KafkyScramMechanism::ScramSha512,
+                        username: "kafka-user".to_string(),
+                        password: KafkySecret::from("kafka-password"),
+                    }),
+                    properties: BTreeMap::new(),
+                },
+                KafkyCredential {
+                    name: "ssl-cred".to_string(),
+                    credential: KafkyCredentialKind::Ssl(KafkySSLCredential {
+                        certificate: KafkyPEM::Path("/my.cert.pem".to_string()),
+                        private_key: Some(KafkyPrivateKey {
+                            key: KafkyPEM::Base64("bXkgcHJpdmF0ZSBrZXk=".to_string()),
+                            password: Some(KafkySecret::from("my-cert-password")),
+                        }),
+                    }),
+                    properties: BTreeMap::new(),
+                },
+            ],
+        };
+        let config = KafkyConfig {
+            path: config_file_path,
+            version: migration::CONFIG_VERSION,
+            include: vec![],
+            default_environment: Some("sample-env".to_string()),
+            environments: vec![env],
+        };
+        let mut config_file = fs::File::create(config_file_path)
+            .map_err(|e| KafkyError::CannotCreateSampleConfig(format!("{}", e)))?;
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        config_file
+            .write(yaml.as_ref())
+            .map_err(|e| KafkyError::CannotCreateSampleConfig(format!("{}", e)))?;
+        Ok(())
+    }
+
+    pub fn create_configuration_sample(
+        config_file: &'a Path,
+    ) -> Result<KafkyConfig<'a>, KafkyError> {
+        print!(
+            "Configuration file {} not found, do you want to create a sample one? [y/N]: ",
+            config_file.display()
+        );
+        let kafky_directory = config_file.parent().unwrap();
+        if !kafky_directory.exists() {
+            create_dir(kafky_directory)?;
+        }
+        stdout().flush().unwrap();
+        let mut answer = String::new();
+        stdin().read_line(&mut answer).unwrap();
+        if answer.trim().eq_ignore_ascii_case("Y") {
+            println!("creating sample..");
+            Self::create_sample(config_file)?;
+            // the sample paths and keys are placeholders, fixed in the editor and checked before connecting
+            let cfg = Self::load(config_file)?;
+            Ok(cfg)
+        } else {
+            Err(KafkyError::ConfigurationNotFound(
+                config_file.as_os_str().to_str().unwrap().to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    #[test]
+    fn parse_test() -> Result<(), KafkyError> {
+        let mut tmp_cfg = tempfile::Builder::new().suffix(".yml").tempfile().unwrap();
+        let yml_cfg = indoc! {"
+            environments:
+              - name: test
+                brokers:
+                  - localhost:9094
+                truststore:
+                  path: ./caroot.cer
+                credentials:
+                  - name: cred
+                    ssl:
+                      certificate:
+                        path: ./producer.cer
+                      privateKey:
+                        path: /producer.pkcs8
+                        password: priv-key-password
+        "};
+        write!(tmp_cfg, "{}", yml_cfg).expect("error writing yml");
+        let cfg = KafkyConfig::load(tmp_cfg.path())?;
+        assert_eq!(cfg.environments.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn create_sample_test() -> Result<(), KafkyError> {
+        let dir = tempfile::tempdir().unwrap();
+        let config_file = dir.path().join("config.yml");
+        KafkyConfig::create_sample(&config_file)?;
+        let cfg = KafkyConfig::load(&config_file)?;
+        assert_eq!(cfg.default_environment, Some("sample-env".to_string()));
+        assert!(cfg.check_environment("sample-env", "plain-cred").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_scram_test() -> Result<(), KafkyError> {
+        let mut tmp_cfg = tempfile::Builder::new().suffix(".yml").tempfile().unwrap();
+        let yml_cfg = indoc! {"
+            environments:
+              - name: test
+                brokers:
+                  - localhost:9094
+                credentials:
+                  - name: scram-256
+                    scram:
+                      mechanism: SCRAM-SHA-256
+                      username: user
+                      password: secret
+                  - name: scram-default
+                    scram:
+                      username: user
+                      password: secret
+        "};
+        write!(tmp_cfg, "{}", yml_cfg).expect("error writing yml");
+        let cfg = KafkyConfig::load(tmp_cfg.path())?;
+        // what runs before a command connects
+        cfg.check_environment("test", "scram-256")?;
+        let env = cfg.get_environment("test").unwrap();
+        match &env.get_credential("scram-256").unwrap().credential {
+            KafkyCredentialKind::Scram(scram) => {
+                assert_eq!(scram.mechanism, KafkyScramMechanism::ScramSha256)
+            }
+            _ => panic!("scram credential expected"),
+        }
+        match &env.get_credential("scram-default").unwrap().credential {
+            KafkyCredentialKind::Scram(scram) => {
+                assert_eq!(scram.mechanism, KafkyScramMechanism::ScramSha512)
+            }
+            _ => panic!("scram credential expected"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn security_protocol_test() -> Result<(), KafkyError> {
+        let mut tmp_cfg = tempfile::Builder::new().suffix(".yml").tempfile().unwrap();
+        let yml_cfg = indoc! {"
+            environments:
+              - name: derived
+                brokers:
+                  - localhost:9094
+                truststore:
+                  path: ./caroot.cer
+                credentials:
+                  - name: plain
+                    plain:
+                      username: user
+                      password: secret
+                  - name: ssl
+                    ssl:
+                      certificate:
+                        path: ./producer.cer
+                      privateKey:
+                        path: /producer.pkcs8
+              - name: overridden
+                brokers:
+                  - localhost:9094
+                securityProtocol: SASL_SSL
+                credentials:
+                  - name: plain
+                    plain:
+                      username: user
+                      password: secret
+                  - name: ssl
+                    ssl:
+                      certificate:
+                        path: ./producer.cer
+                      privateKey:
+                        path: /producer.pkcs8
+        "};
+        write!(tmp_cfg, "{}", yml_cfg).expect("error writing yml");
+        let cfg = KafkyConfig::load(tmp_cfg.path())?;
+
+        let derived = cfg.get_environment("derived").unwrap();
+        assert_eq!(
+            derived.security_protocol(derived.get_credential("plain").unwrap())?,
+            KafkySecurityProtocol::SaslSsl
+        );
+        assert_eq!(
+            derived.security_protocol(derived.get_credential("ssl").unwrap())?,
+            KafkySecurityProtocol::Ssl
+        );
+
+        let overridden = cfg.get_environment("overridden").unwrap();
+        assert_eq!(
+            overridden.security_protocol(overridden.get_credential("plain").unwrap())?,
+            KafkySecurityProtocol::SaslSsl
+        );
+        assert!(matches!(
+            overridden.security_protocol(overridden.get_credential("ssl").unwrap()),
+            Err(KafkyError::InvalidSecurityProtocol(..))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_secret_test() -> Result<(), KafkyError> {
+        let mut tmp_cfg = tempfile::Builder::new().suffix(".yml").tempfile().unwrap();
+        let yml_cfg = indoc! {"
+            environments:
+              - name: test
+                brokers:
+                  - localhost:9094
+                credentials:
+                  - name: env
+                    plain:
+                      username: user
+                      password:
+                        env: KAFKA_PASSWORD
+                  - name: command
+                    scram:
+                      username: user
+                      password:
+                        command: pass show kafka/prod
+                  - name: ssl
+                    ssl:
+                      certificate:
+                        path: ./producer.cer
+                      privateKey:
+                        path: /producer.pkcs8
+                        password:
+                          file: /secrets/key-password
+        "};
+        write!(tmp_cfg, "{}", yml_cfg).expect("error writing yml");
+        let cfg = KafkyConfig::load(tmp_cfg.path())?;
+        let env = cfg.get_environment("test").unwrap();
+        match &env.get_credential("env").unwrap().credential {
+            KafkyCredentialKind::Plain(plain) => assert_eq!(
+                plain.password,
+                KafkySecret::Reference(KafkySecretReference::Env("KAFKA_PASSWORD".to_string()))
+            ),
+            _ => panic!("plain credential expected"),
+        }
+        match &env.get_credential("command").unwrap().credential {
+            KafkyCredentialKind::Scram(scram) => assert_eq!(
+                scram.password,
+                KafkySecret::Reference(KafkySecretReference::Command(
+                    "pass show kafka/prod".to_string()
+                ))
+            ),
+            _ => panic!("scram credential expected"),
+        }
+        match &env.get_credential("ssl").unwrap().credential {
+            KafkyCredentialKind::Ssl(ssl) => assert_eq!(
+                ssl.private_key.as_ref().unwrap().password,
+                Some(KafkySecret::Reference(KafkySecretReference::File(
+                    "/secrets/key-password".to_string()
+                )))
+            ),
+            _ => panic!("ssl credential expected"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn read_only_test() -> Result<(), KafkyError> {
+        let mut tmp_cfg = tempfile::Builder::new().suffix(".yml").tempfile().unwrap();
+        let yml_cfg = indoc! {"
+            environments:
+              - name: prod
+                brokers:
+                  - localhost:9094
+                readOnly: true
+                protected: true
+              - name: dev
+                brokers:
+                  - localhost:9094
+        "};
+        write!(tmp_cfg, "{}", yml_cfg).expect("error writing yml");
+        let cfg = KafkyConfig::load(tmp_cfg.path())?;
+
+        let prod = cfg.get_environment("prod").unwrap();
+        assert!(prod.protected);
+        assert!(matches!(
+            prod.check_writable("produce"),
+            Err(KafkyError::ReadOnlyEnvironment(_, _))
+        ));
+        let dev = cfg.get_environment("dev").unwrap();
+        assert!(!dev.protected);
+        assert!(dev.check_writable("produce").is_ok());
+        Ok(())
+    }
+}
@@ -0,0 +1,618 @@
+use std::collections::BTreeMap;
+
+use crate::config::{
+    KafkyCredential, KafkyCredentialKind, KafkyEnvironment, KafkyKeystore, KafkyKeystoreFormat,
+    KafkyKeystoreSource, KafkyOAuthBearerCredential, KafkyPEM, KafkyPlainCredential,
+    KafkyPrivateKey, KafkySSLCredential, KafkyScramCredential, KafkyScramMechanism, KafkySecret,
+    KafkySecretResolver, KafkySecurityProtocol,
+};
+use crate::errors::KafkyError;
+
+const PLAIN_LOGIN_MODULE: &str = "org.apache.kafka.common.security.plain.PlainLoginModule";
+const SCRAM_LOGIN_MODULE: &str = "org.apache.kafka.common.security.scram.ScramLoginModule";
+const OAUTHBEARER_LOGIN_MODULE: &str =
+    "org.apache.kafka.common.security.oauthbearer.OAuthBearerLoginModule";
+const OAUTHBEARER_CALLBACK_HANDLER: &str =
+    "org.apache.kafka.common.security.oauthbearer.OAuthBearerLoginCallbackHandler";
+
+/// Kafka client properties, as read by the Java tools
+pub type KafkyProperties = BTreeMap<String, String>;
+
+/// parses a java .properties content: comments, `=`/`:` separators, line continuations and escapes
+pub fn parse_properties(content: &str) -> KafkyProperties {
+    let mut properties = KafkyProperties::new();
+    let mut logical_line = String::new();
+    for line in content.lines() {
+        let line = if logical_line.is_empty() {
+            line.trim_start()
+        } else {
+            line.trim_start_matches(|c: char| c.is_whitespace())
+        };
+        if logical_line.is_empty() && (line.is_empty() || line.starts_with(['#', '!'])) {
+            continue;
+        }
+        let trailing_backslashes = line.chars().rev().take_while(|c| *c == '\\').count();
+        if trailing_backslashes % 2 == 1 {
+            logical_line.push_str(&line[..line.len() - 1]);
+            continue;
+        }
+        logical_line.push_str(line);
+        let (key, value) = split_property(&logical_line);
+        properties.insert(key, value);
+        logical_line.clear();
+    }
+    if !logical_line.is_empty() {
+        let (key, value) = split_property(&logical_line);
+        properties.insert(key, value);
+    }
+    properties
+}
+
+fn split_property(line: &str) -> (String, String) {
+    let mut escaped = false;
+    let mut key_end = line.len();
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '=' || c == ':' || c.is_whitespace() {
+            key_end = i;
+            break;
+        }
+    }
+    let rest = line[key_end..].trim_start();
+    let rest = rest
+        .strip_prefix(['=', ':'])
+        .map(|r| r.trim_start())
+        .unwrap_or(rest);
+    (unescape(&line[..key_end]), unescape(rest))
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('f') => result.push('\u{c}'),
+            Some('u') => {
+                let code: String = chars.by_ref().take(4).collect();
+                match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                    Some(unicode) => result.push(unicode),
+                    None => result.push_str(&code),
+                }
+            }
+            Some(other) => result.push(other),
+            None => {}
+        }
+    }
+    result
+}
+
+/// parses a `key=value` librdkafka property given on the command line
+pub fn parse_property(property: &str) -> Result<(String, String), KafkyError> {
+    match property.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(KafkyError::ParseError(format!(
+            "invalid property {}, expected key=value",
+            property
+        ))),
+    }
+}
+
+/// formats the properties in the java .properties syntax
+pub fn write_properties(properties: &[(String, String)]) -> String {
+    properties
+        .iter()
+        .map(|(key, value)| {
+            let key = key
+                .replace('\\', "\\\\")
+                .replace(' ', "\\ ")
+                .replace('=', "\\=")
+                .replace(':', "\\:");
+            let mut value = value
+                .replace('\\', "\\\\")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r");
+            if value.starts_with(' ') {
+                value.insert(0, '\\');
+            }
+            format!("{}={}\n", key, value)
+        })
+        .collect()
+}
+
+/// login module and options of a `sasl.jaas.config` value
+fn parse_jaas(jaas: &str) -> Result<(String, BTreeMap<String, String>), String> {
+    let mut tokens = jaas
+        .trim()
+        .trim_end_matches(';')
+        .trim()
+        .splitn(3, char::is_whitespace);
+    let login_module = tokens.next().unwrap_or_default().to_string();
+    let _control_flag = tokens.next();
+    let mut options = BTreeMap::new();
+    let mut rest = tokens.next().unwrap_or_default().trim_start();
+    while !rest.is_empty() {
+        let (key, after_key) = rest
+            .split_once('=')
+            .ok_or_else(|| format!("invalid sasl.jaas.config option {}", rest))?;
+        let after_key = after_key.trim_start();
+        let (value, after_value) = match after_key.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = None;
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            if let Some((_, escaped)) = chars.next() {
+                                value.push(escaped);
+                            }
+                        }
+                        '"' => {
+                            end = Some(i + 1);
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                let end = end.ok_or("unterminated quoted value in sasl.jaas.config")?;
+                (value, &quoted[end..])
+            }
+            None => {
+                let end = after_key
+                    .find(char::is_whitespace)
+                    .unwrap_or(after_key.len());
+                (after_key[..end].to_string(), &after_key[end..])
+            }
+        };
+        options.insert(key.trim().to_string(), value);
+        rest = after_value.trim_start();
+    }
+    Ok((login_module, options))
+}
+
+fn jaas_config(login_module: &str, options: &[(&str, &str)]) -> String {
+    let options: Vec<String> = options
+        .iter()
+        .map(|(key, value)| {
+            format!(
+                "{}=\"{}\"",
+                key,
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            )
+        })
+        .collect();
+    format!("{} required {};", login_module, options.join(" "))
+}
+
+fn keystore_pem(
+    properties: &KafkyProperties,
+    prefix: &str,
+    location: &str,
+) -> Result<KafkyPEM, String> {
+    let property = |name: &str| properties.get(&format!("ssl.{}.{}", prefix, name));
+    let keystore = || KafkyKeystore {
+        store: KafkyKeystoreSource::Path(location.to_string()),
+        password: property("password").map(|p| KafkySecret::from(p.as_str())),
+        key_password: None,
+        alias: None,
+    };
+    match property("type").map(|t| t.to_uppercase()).as_deref() {
+        None | Some("JKS") => Ok(KafkyPEM::Jks(keystore())),
+        Some("PKCS12") => Ok(KafkyPEM::Pkcs12(keystore())),
+        Some("PEM") => Ok(KafkyPEM::Path(location.to_string())),
+        Some(other) => Err(format!("unsupported ssl.{}.type {}", prefix, other)),
+    }
+}
+
+impl KafkyEnvironment {
+    /// builds an environment with a single credential from Kafka client properties
+    pub fn from_properties(
+        name: &str,
+        credential_name: &str,
+        properties: &KafkyProperties,
+    ) -> Result<Self, String> {
+        let property = |key: &str| properties.get(key).map(|v| v.trim());
+        let brokers: Vec<String> = property("bootstrap.servers")
+            .ok_or("bootstrap.servers not found")?
+            .split(',')
+            .map(|b| b.trim().to_string())
+            .filter(|b| !b.is_empty())
+            .collect();
+        let security_protocol = match property("security.protocol")
+            .unwrap_or("PLAINTEXT")
+            .to_uppercase()
+            .as_str()
+        {
+            "PLAINTEXT" => KafkySecurityProtocol::Plaintext,
+            "SSL" => KafkySecurityProtocol::Ssl,
+            "SASL_PLAINTEXT" => KafkySecurityProtocol::SaslPlaintext,
+            "SASL_SSL" => KafkySecurityProtocol::SaslSsl,
+            other => return Err(format!("unknown security.protocol {}", other)),
+        };
+
+        let truststore = if let Some(certificates) = property("ssl.truststore.certificates") {
+            Some(KafkyPEM::Pem(certificates.to_string()))
+        } else if let Some(location) = property("ssl.truststore.location") {
+            Some(keystore_pem(properties, "truststore", location)?)
+        } else {
+            None
+        };
+
+        let credential = if security_protocol.is_sasl() {
+            Self::sasl_credential_from_properties(properties)?
+        } else if security_protocol.is_ssl() {
+            Self::ssl_credential_from_properties(properties)?
+        } else {
+            return Err(
+                "PLAINTEXT clusters without authentication cannot be described with a kafky credential"
+                    .to_string(),
+            );
+        };
+
+        let mut environment = KafkyEnvironment {
+            name: name.to_string(),
+            brokers,
+            credentials: vec![KafkyCredential {
+                name: credential_name.to_string(),
+                credential,
+                properties: BTreeMap::new(),
+            }],
+            truststore,
+            security_protocol: None,
+            properties: BTreeMap::new(),
+            default_credential: None,
+            read_only: false,
+            protected: false,
+            schema_registry: None,
+        };
+        // the protocol is written only when it differs from the one derived by kafky
+        if environment
+            .security_protocol(&environment.credentials[0])
+            .ok()
+            != Some(security_protocol)
+        {
+            environment.security_protocol = Some(security_protocol);
+        }
+        Ok(environment)
+    }
+
+    fn sasl_credential_from_properties(
+        properties: &KafkyProperties,
+    ) -> Result<KafkyCredentialKind, String> {
+        let mechanism = properties
+            .get("sasl.mechanism")
+            .map(|m| m.trim().to_uppercase())
+            .unwrap_or_else(|| "GSSAPI".to_string());
+        let (_, options) = parse_jaas(
+            properties
+                .get("sasl.jaas.config")
+                .ok_or("sasl.jaas.config not found")?,
+        )?;
+        let option = |key: &str| {
+            options
+                .get(key)
+                .cloned()
+                .ok_or_else(|| format!("{} not found in sasl.jaas.config", key))
+        };
+        match mechanism.as_str() {
+            "PLAIN" => Ok(KafkyCredentialKind::Plain(KafkyPlainCredential {
+                username: option("username")?,
+                password: KafkySecret::Value(option("password")?),
+            })),
+            "SCRAM-SHA-256" | "SCRAM-SHA-512" => {
+                Ok(KafkyCredentialKind::Scram(KafkyScramCredential {
+                    mechanism: if mechanism == "SCRAM-SHA-256" {
+                        KafkyScramMechanism::ScramSha256
+                    } else {
+                        KafkyScramMechanism::ScramSha512
+                    },
+                    username: option("username")?,
+                    password: KafkySecret::Value(option("password")?),
+                }))
+            }
+            "OAUTHBEARER" => Ok(KafkyCredentialKind::OAuthBearer(
+                KafkyOAuthBearerCredential {
+                    client_id: option("clientId")?,
+                    client_secret: KafkySecret::Value(option("clientSecret")?),
+                    token_endpoint_url: properties
+                        .get("sasl.oauthbearer.token.endpoint.url")
+                        .cloned()
+                        .ok_or("sasl.oauthbearer.token.endpoint.url not found")?,
+                    scope: options.get("scope").cloned(),
+                    extensions: options
+                        .iter()
+                        .filter_map(|(key, value)| {
+                            key.strip_prefix("extension_")
+                                .map(|key| (key.to_string(), value.clone()))
+                        })
+                        .collect(),
+                },
+            )),
+            other => Err(format!("unsupported sasl.mechanism {}", other)),
+        }
+    }
+
+    fn ssl_credential_from_properties(
+        properties: &KafkyProperties,
+    ) -> Result<KafkyCredentialKind, String> {
+        let key_password = properties
+            .get("ssl.key.password")
+            .map(|p| KafkySecret::from(p.as_str()));
+        if let (Some(chain), Some(key)) = (
+            properties.get("ssl.keystore.certificate.chain"),
+            properties.get("ssl.keystore.key"),
+        ) {
+            return Ok(KafkyCredentialKind::Ssl(KafkySSLCredential {
+                certificate: KafkyPEM::Pem(chain.clone()),
+                private_key: Some(KafkyPrivateKey {
+                    key: KafkyPEM::Pem(key.clone()),
+                    password: key_password,
+                }),
+            }));
+        }
+        let location = properties.get("ssl.keystore.location").ok_or(
+            "SSL without ssl.keystore.location cannot be described with a kafky credential",
+        )?;
+        let ssl = match keystore_pem(properties, "keystore", location)? {
+            KafkyPEM::Pkcs12(mut keystore) => {
+                keystore.key_password = key_password;
+                KafkySSLCredential {
+                    certificate: KafkyPEM::Pkcs12(keystore),
+                    private_key: None,
+                }
+            }
+            KafkyPEM::Jks(mut keystore) => {
+                keystore.key_password = key_password;
+                KafkySSLCredential {
+                    certificate: KafkyPEM::Jks(keystore),
+                    private_key: None,
+                }
+            }
+            pem => KafkySSLCredential {
+                certificate: pem,
+                private_key: Some(KafkyPrivateKey {
+                    key: KafkyPEM::Path(location.clone()),
+                    password: key_password,
+                }),
+            },
+        };
+        Ok(KafkyCredentialKind::Ssl(ssl))
+    }
+
+    /// Kafka client properties equivalent to the environment with the credential, secrets included
+    pub fn to_properties(
+        &self,
+        credential: &KafkyCredential,
+        resolver: &KafkySecretResolver,
+    ) -> Result<Vec<(String, String)>, String> {
+        let security_protocol = self
+            .security_protocol(credential)
+            .map_err(|e| e.to_string())?;
+        let mut properties = vec![
+            ("bootstrap.servers".to_string(), self.brokers.join(",")),
+            (
+                "security.protocol".to_string(),
+                security_protocol.to_string(),
+            ),
+        ];
+        let mut add = |key: &str, value: String| properties.push((key.to_string(), value));
+
+        if let Some(truststore) = &self.truststore {
+            match truststore.keystore() {
+                Some((format, keystore)) => {
+                    Self::keystore_properties(&mut add, "truststore", format, keystore, resolver)?
+                }
+                None => {
+                    add("ssl.truststore.type", "PEM".to_string());
+                    match truststore {
+                        KafkyPEM::Path(path) => add("ssl.truststore.location", path.clone()),
+                        _ => add(
+                            "ssl.truststore.certificates",
+                            pem_string(truststore.read()?)?,
+                        ),
+                    }
+                }
+            }
+        }
+
+        match &credential.credential {
+            KafkyCredentialKind::Plain(plain) => {
+                add("sasl.mechanism", "PLAIN".to_string());
+                let password = resolver.resolve(&plain.password)?;
+                add(
+                    "sasl.jaas.config",
+                    jaas_config(
+                        PLAIN_LOGIN_MODULE,
+                        &[("username", &plain.username), ("password", &password)],
+                    ),
+                );
+            }
+            KafkyCredentialKind::Scram(scram) => {
+                add(
+                    "sasl.mechanism",
+                    scram.mechanism.sasl_mechanism().to_string(),
+                );
+                let password = resolver.resolve(&scram.password)?;
+                add(
+                    "sasl.jaas.config",
+                    jaas_config(
+                        SCRAM_LOGIN_MODULE,
+                        &[("username", &scram.username), ("password", &password)],
+                    ),
+                );
+            }
+            KafkyCredentialKind::OAuthBearer(oauth) => {
+                add("sasl.mechanism", "OAUTHBEARER".to_string());
+                add(
+                    "sasl.login.callback.handler.class",
+                    OAUTHBEARER_CALLBACK_HANDLER.to_string(),
+                );
+                add(
+                    "sasl.oauthbearer.token.endpoint.url",
+                    oauth.token_endpoint_url.clone(),
+                );
+                let client_secret = resolver.resolve(&oauth.client_secret)?;
+                let mut options = vec![
+                    ("clientId", oauth.client_id.as_str()),
+                    ("clientSecret", client_secret.as_str()),
+                ];
+                if let Some(scope) = &oauth.scope {
+                    options.push(("scope", scope));
+                }
+                let extensions: Vec<(String, &str)> = oauth
+                    .extensions
+                    .iter()
+                    .map(|(key, value)| (format!("extension_{}", key), value.as_str()))
+                    .collect();
+                options.extend(extensions.iter().map(|(key, value)| (key.as_str(), *value)));
+                add(
+                    "sasl.jaas.config",
+                    jaas_config(OAUTHBEARER_LOGIN_MODULE, &options),
+                );
+            }
+            KafkyCredentialKind::Ssl(ssl) => match (ssl.certificate.keystore(), &ssl.private_key) {
+                (Some((format, keystore)), None) => {
+                    Self::keystore_properties(&mut add, "keystore", format, keystore, resolver)?
+                }
+                (_, private_key) => {
+                    let (certificate, key) = match private_key {
+                        Some(private_key) => {
+                            if let Some(password) = &private_key.password {
+                                add("ssl.key.password", resolver.resolve(password)?);
+                            }
+                            (ssl.certificate.read()?, private_key.key.read()?)
+                        }
+                        None => return Err("privateKey not found".to_string()),
+                    };
+                    add("ssl.keystore.type", "PEM".to_string());
+                    add("ssl.keystore.certificate.chain", pem_string(certificate)?);
+                    add("ssl.keystore.key", pem_string(key)?);
+                }
+            },
+        }
+        Ok(properties)
+    }
+
+    fn keystore_properties<F: FnMut(&str, String)>(
+        add: &mut F,
+        prefix: &str,
+        format: KafkyKeystoreFormat,
+        keystore: &KafkyKeystore,
+        resolver: &KafkySecretResolver,
+    ) -> Result<(), String> {
+        let location = match &keystore.store {
+            KafkyKeystoreSource::Path(path) => path,
+            KafkyKeystoreSource::Base64(_) => {
+                return Err(format!(
+                    "inline base64 {} cannot be exported, use a path",
+                    prefix
+                ))
+            }
+        };
+        add(
+            &format!("ssl.{}.type", prefix),
+            match format {
+                KafkyKeystoreFormat::Pkcs12 => "PKCS12",
+                KafkyKeystoreFormat::Jks => "JKS",
+            }
+            .to_string(),
+        );
+        add(&format!("ssl.{}.location", prefix), location.clone());
+        if let Some(password) = &keystore.password {
+            add(
+                &format!("ssl.{}.password", prefix),
+                resolver.resolve(password)?,
+            );
+        }
+        if let Some(key_password) = &keystore.key_password {
+            add("ssl.key.password", resolver.resolve(key_password)?);
+        }
+        Ok(())
+    }
+}
+
+fn pem_string(content: Vec<u8>) -> Result<String, String> {
+    String::from_utf8(content)
+        .map(|pem| pem.trim_end().to_string())
+        .map_err(|e| format!("invalid PEM content: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn parse_properties_test() {
+        let properties = parse_properties(indoc! {r#"
+            # comment
+            ! other comment
+            bootstrap.servers = kafka-1:9093,\
+                                kafka-2:9093
+            security.protocol:SASL_SSL
+            sasl.mechanism SCRAM-SHA-256
+            sasl.jaas.config=org.apache.kafka.common.security.scram.ScramLoginModule required \
+                username="app" password="p\\"wd";
+            ssl.truststore.location=/etc/kafka/truststore.p12
+            ssl.truststore.type=PKCS12
+            ssl.truststore.password=changeit
+            escaped\ key=line\nbreak
+        "#});
+        assert_eq!(
+            properties.get("bootstrap.servers").unwrap(),
+            "kafka-1:9093,kafka-2:9093"
+        );
+        assert_eq!(properties.get("security.protocol").unwrap(), "SASL_SSL");
+        assert_eq!(properties.get("sasl.mechanism").unwrap(), "SCRAM-SHA-256");
+        assert_eq!(properties.get("escaped key").unwrap(), "line\nbreak");
+
+        let environment = KafkyEnvironment::from_properties("prod", "app", &properties).unwrap();
+        assert_eq!(environment.brokers, vec!["kafka-1:9093", "kafka-2:9093"]);
+        assert_eq!(environment.security_protocol, None);
+        match &environment.truststore {
+            Some(KafkyPEM::Pkcs12(keystore)) => {
+                assert_eq!(keystore.password, Some(KafkySecret::from("changeit")))
+            }
+            other => panic!("pkcs12 truststore expected, found {:?}", other),
+        }
+        match &environment.credentials[0].credential {
+            KafkyCredentialKind::Scram(scram) => {
+                assert_eq!(scram.mechanism, KafkyScramMechanism::ScramSha256);
+                assert_eq!(scram.username, "app");
+                assert_eq!(scram.password, KafkySecret::from("p\"wd"));
+            }
+            other => panic!("scram credential expected, found {:?}", other),
+        }
+
+        let resolver = KafkySecretResolver::new(std::path::Path::new("secrets.enc"));
+        let exported = environment
+            .to_properties(&environment.credentials[0], &resolver)
+            .unwrap();
+        let reimported = parse_properties(&write_properties(&exported));
+        assert_eq!(
+            reimported.get("ssl.truststore.location"),
+            properties.get("ssl.truststore.location")
+        );
+        assert_eq!(reimported.get("sasl.jaas.config").unwrap(), "org.apache.kafka.common.security.scram.ScramLoginModule required username=\"app\" password=\"p\\\"wd\";");
+        assert_eq!(
+            KafkyEnvironment::from_properties("prod", "app", &reimported)
+                .unwrap()
+                .credentials[0]
+                .credential
+                .kind_name(),
+            "scram"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::config::vault::KafkyVault;
+
+/// A secret value, either inline or resolved when the client is created
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum KafkySecret {
+    Value(String),
+    Reference(KafkySecretReference),
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum KafkySecretReference {
+    /// environment variable name
+    Env(String),
+    /// file containing the secret, a trailing new line is ignored
+    File(String),
+    /// shell command printing the secret on the standard output
+    Command(String),
+    /// entry name in the kafky vault
+    Vault(String),
+}
+
+impl fmt::Display for KafkySecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KafkySecret::Value(_) => write!(f, "inline value"),
+            KafkySecret::Reference(KafkySecretReference::Env(var)) => write!(f, "env {}", var),
+            KafkySecret::Reference(KafkySecretReference::File(path)) => write!(f, "file {}", path),
+            KafkySecret::Reference(KafkySecretReference::Command(cmd)) => {
+                write!(f, "command \"{}\"", cmd)
+            }
+            KafkySecret::Reference(KafkySecretReference::Vault(name)) => {
+                write!(f, "vault entry {}", name)
+            }
+        }
+    }
+}
+
+impl From<&str> for KafkySecret {
+    fn from(value: &str) -> Self {
+        KafkySecret::Value(value.to_string())
+    }
+}
+
+/// Resolves secrets, each reference once: a command runs and the vault is unlocked only the
+/// first time one of their secrets is needed
+pub struct KafkySecretResolver {
+    vault_path: PathBuf,
+    vault: Mutex<Option<KafkyVault>>,
+    resolved: Mutex<HashMap<KafkySecretReference, String>>,
+}
+
+impl KafkySecretResolver {
+    pub fn new(vault_path: &Path) -> Self {
+        KafkySecretResolver {
+            vault_path: vault_path.to_path_buf(),
+            vault: Mutex::new(None),
+            resolved: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn resolve(&self, secret: &KafkySecret) -> Result<String, String> {
+        match secret {
+            KafkySecret::Value(value) => Ok(value.clone()),
+            KafkySecret::Reference(reference) => {
+                if let Some(value) = self.resolved.lock().unwrap().get(reference) {
+                    return Ok(value.clone());
+                }
+                debug!("resolving secret from {}", secret);
+                let value = self
+                    .resolve_reference(reference)
+                    .map_err(|e| format!("cannot read the secret from {}: {}", secret, e))?;
+                self.resolved
+                    .lock()
+                    .unwrap()
+                    .insert(reference.clone(), value.clone());
+                Ok(value)
+            }
+        }
+    }
+
+    fn resolve_reference(&self, reference: &KafkySecretReference) -> Result<String, String> {
+        match reference {
+            KafkySecretReference::Env(var) => std::env::var(var).map_err(|e| e.to_string()),
+            KafkySecretReference::File(path) => fs::read_to_string(path)
+                .map(|content| Self::trim_new_line(&content))
+                .map_err(|e| e.to_string()),
+            KafkySecretReference::Command(cmd) => {
+                let sh_path = which::which("sh").map_err(|e| e.to_string())?;
+                let output = Command::new(sh_path)
+                    .arg("-c")
+                    .arg(cmd)
+                    .output()
+                    .map_err(|e| e.to_string())?;
+                if !output.status.success() {
+                    return Err(format!(
+                        "{}, {}",
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ));
+                }
+                String::from_utf8(output.stdout)
+                    .map(|stdout| Self::trim_new_line(&stdout))
+                    .map_err(|e| e.to_string())
+            }
+            KafkySecretReference::Vault(name) => {
+                let mut vault = self.vault.lock().unwrap();
+                if vault.is_none() {
+                    if !self.vault_path.exists() {
+                        return Err(format!("vault {} not found", self.vault_path.display()));
+                    }
+                    *vault = Some(KafkyVault::unlock(&self.vault_path).map_err(|e| e.to_string())?);
+                }
+                vault
+                    .as_ref()
+                    .and_then(|v| v.get(name))
+                    .map(|s| s.to_string())
+                    .ok_or_else(|| "entry not found".to_string())
+            }
+        }
+    }
+
+    fn trim_new_line(value: &str) -> String {
+        value
+            .strip_suffix('\n')
+            .map(|v| v.strip_suffix('\r').unwrap_or(v))
+            .unwrap_or(value)
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::vault::VAULT_PASSPHRASE_ENV;
+    use crate::KafkyError;
+    use std::io::Write;
+
+    #[test]
+    fn resolve_test() -> Result<(), KafkyError> {
+        let vault_dir = tempfile::tempdir()?;
+        let vault_path = vault_dir.path().join("secrets.enc");
+        let mut vault = KafkyVault::open(&vault_path, "master")?;
+        vault.set("kafka-password", "from-vault");
+        vault.save()?;
+        std::env::set_var(VAULT_PASSPHRASE_ENV, "master");
+        let resolver = KafkySecretResolver::new(&vault_path);
+
+        assert_eq!(
+            resolver.resolve(&KafkySecret::from("inline")),
+            Ok("inline".to_string())
+        );
+
+        std::env::set_var("KAFKY_SECRET_TEST", "from-env");
+        let env_secret =
+            KafkySecret::Reference(KafkySecretReference::Env("KAFKY_SECRET_TEST".to_string()));
+        assert_eq!(resolver.resolve(&env_secret), Ok("from-env".to_string()));
+
+        let mut secret_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(secret_file, "from-file").unwrap();
+        let file_secret = KafkySecret::Reference(KafkySecretReference::File(
+            secret_file.path().to_str().unwrap().to_string(),
+        ));
+        assert_eq!(resolver.resolve(&file_secret), Ok("from-file".to_string()));
+
+        let command_secret = KafkySecret::Reference(KafkySecretReference::Command(
+            "echo from-command".to_string(),
+        ));
+        assert_eq!(
+            resolver.resolve(&command_secret),
+            Ok("from-command".to_string())
+        );
+
+        // a command runs once per resolver
+        let counter_file = tempfile::NamedTempFile::new().unwrap();
+        let counting_secret = KafkySecret::Reference(KafkySecretReference::Command(format!(
+            "echo run >> {} && echo counted",
+            counter_file.path().display()
+        )));
+        assert_eq!(
+            resolver.resolve(&counting_secret),
+            Ok("counted".to_string())
+        );
+        assert_eq!(
+            resolver.resolve(&counting_secret),
+            Ok("counted".to_string())
+        );
+        assert_eq!(fs::read_to_string(counter_file.path())?, "run\n");
+
+        let failing_secret =
+            KafkySecret::Reference(KafkySecretReference::Command("exit 3".to_string()));
+        assert!(resolver.resolve(&failing_secret).is_err());
+
+        let vault_secret =
+            KafkySecret::Reference(KafkySecretReference::Vault("kafka-password".to_string()));
+        assert_eq!(
+            resolver.resolve(&vault_secret),
+            Ok("from-vault".to_string())
+        );
+        let missing_vault_secret =
+            KafkySecret::Reference(KafkySecretReference::Vault("missing".to_string()));
+        assert!(resolver.resolve(&missing_vault_secret).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::config::{KafkyConfig, KafkyCredentialKind, KafkyEnvironment, KafkyPEM};
+use crate::errors::KafkyError;
+
+/// Configuration problem located by its YAML path, e.g. `environments[0].credentials[1].name`
+#[derive(Debug, PartialEq, Serialize)]
+pub struct KafkyConfigProblem {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for KafkyConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+#[derive(Default)]
+struct Problems(Vec<KafkyConfigProblem>);
+
+impl Problems {
+    fn add<P: Into<String>, M: Into<String>>(&mut self, path: P, message: M) {
+        self.0.push(KafkyConfigProblem {
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+
+    fn check_pem(&mut self, path: &str, pem: &KafkyPEM) {
+        let path = match pem {
+            KafkyPEM::Path(_) => format!("{}.path", path),
+            KafkyPEM::Base64(_) => format!("{}.base64", path),
+            KafkyPEM::Pem(_) => format!("{}.pem", path),
+            KafkyPEM::Pkcs12(keystore) => format!("{}.pkcs12.{}", path, keystore.store.key()),
+            KafkyPEM::Jks(keystore) => format!("{}.jks.{}", path, keystore.store.key()),
+        };
+        let result = pem.read().and_then(|content| {
+            let is_pem = String::from_utf8_lossy(&content).contains("-----BEGIN ");
+            match pem.keystore() {
+                None if !is_pem => Err("not a PEM content".to_string()),
+                _ => Ok(()),
+            }
+        });
+        if let Err(e) = result {
+            self.add(path, e);
+        }
+    }
+}
+
+impl<'a> KafkyConfig<'a> {
+    /// checks what deserialization can't: duplicates, empty lists and unreadable certificates
+    pub fn validate(&self) -> Vec<KafkyConfigProblem> {
+        let mut problems = Problems::default();
+        if self.environments.is_empty() {
+            problems.add("environments", "at least one environment is required");
+        }
+        let mut names = HashSet::new();
+        for (i, environment) in self.environments.iter().enumerate() {
+            let path = format!("environments[{}]", i);
+            if !names.insert(environment.name.as_str()) {
+                problems.add(
+                    format!("{}.name", path),
+                    format!("duplicated environment name {}", environment.name),
+                );
+            }
+            environment.validate(&path, None, &mut problems);
+        }
+        if let Some(default_environment) = &self.default_environment {
+            if self.get_environment(default_environment).is_none() {
+                problems.add(
+                    "defaultEnvironment",
+                    format!("environment {} not found", default_environment),
+                );
+            }
+        }
+        problems.0
+    }
+
+    /// validation problems of the environment and the credential a command connects with,
+    /// as an InvalidConfiguration error: the other ones may be broken without blocking it
+    pub fn check_environment(&self, environment: &str, credential: &str) -> Result<(), KafkyError> {
+        let mut problems = Problems::default();
+        if let Some((i, environment)) = self
+            .environments
+            .iter()
+            .enumerate()
+            .find(|(_, e)| e.name == environment)
+        {
+            environment.validate(
+                &format!("environments[{}]", i),
+                Some(credential),
+                &mut problems,
+            );
+        }
+        let problems = problems.0;
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(KafkyError::InvalidConfiguration(format!(
+            "{} ({}), run `kafky config validate` for details",
+            self.path().display(),
+            problems
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<String>>()
+                .join("; ")
+        )))
+    }
+}
+
+impl KafkyEnvironment {
+    /// with `selected_credential`, the other credentials are not checked
+    fn validate(&self, path: &str, selected_credential: Option<&str>, problems: &mut Problems) {
+        if self.name.trim().is_empty() {
+            problems.add(format!("{}.name", path), "empty environment name");
+        }
+        if self.brokers.is_empty() {
+            problems.add(
+                format!("{}.brokers", path),
+                "at least one broker is required",
+            );
+        }
+        for (i, broker) in self.brokers.iter().enumerate() {
+            if broker.trim().is_empty() {
+                problems.add(format!("{}.brokers[{}]", path, i), "empty broker address");
+            }
+        }
+        if let Some(truststore) = &self.truststore {
+            problems.check_pem(&format!("{}.truststore", path), truststore);
+        }
+        if let Some(schema_registry) = &self.schema_registry {
+            if !schema_registry.url.starts_with("http://")
+                && !schema_registry.url.starts_with("https://")
+            {
+                problems.add(
+                    format!("{}.schemaRegistry.url", path),
+                    "http or https url expected",
+                );
+            }
+            if schema_registry.username.is_some() != schema_registry.password.is_some() {
+                problems.add(
+                    format!("{}.schemaRegistry", path),
+                    "username and password must be set together",
+                );
+            }
+        }
+        if self.credentials.is_empty() {
+            problems.add(
+                format!("{}.credentials", path),
+                "at least one credential is required",
+            );
+        }
+        if let Some(default_credential) = &self.default_credential {
+            if self.get_credential(default_credential).is_none() {
+                problems.add(
+                    format!("{}.defaultCredential", path),
+                    format!("credential {} not found", default_credential),
+                );
+            }
+        }
+        let mut names = HashSet::new();
+        for (i, credential) in self.credentials.iter().enumerate() {
+            let path = format!("{}.credentials[{}]", path, i);
+            if !names.insert(credential.name.as_str()) {
+                problems.add(
+                    format!("{}.name", path),
+                    format!("duplicated credential name {}", credential.name),
+                );
+            }
+            if selected_credential.is_some_and(|selected| selected != credential.name) {
+                continue;
+            }
+            if let Err(e) = self.security_protocol(credential) {
+                problems.add(path.clone(), e.to_string());
+            }
+            if let KafkyCredentialKind::Ssl(ssl) = &credential.credential {
+                problems.check_pem(&format!("{}.ssl.certificate", path), &ssl.certificate);
+                match &ssl.private_key {
+                    Some(private_key) if private_key.key.keystore().is_some() => {
+                        problems.add(
+                            format!("{}.ssl.privateKey", path),
+                            "keystores must be configured as certificate",
+                        );
+                    }
+                    Some(private_key) => {
+                        problems.check_pem(&format!("{}.ssl.privateKey", path), &private_key.key)
+                    }
+                    None if ssl.certificate.keystore().is_none() => {
+                        problems.add(
+                            format!("{}.ssl.privateKey", path),
+                            "privateKey is required unless the certificate is a keystore",
+                        );
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use std::io::Write;
+
+    #[test]
+    fn validate_test() {
+        let mut tmp_cfg = tempfile::Builder::new().suffix(".yml").tempfile().unwrap();
+        write!(
+            tmp_cfg,
+            indoc! {"
+                environments:
+                  - name: dev
+                    brokers: []
+                    truststore:
+                      base64: not base64!
+                    credentials:
+                      - name: ssl
+                        ssl:
+                          certificate:
+                            path: /not/existing.pem
+                      - name: ssl
+                        plain:
+                          username: user
+                          password: password
+                  - name: dev
+                    brokers:
+                      - localhost:9092
+                    credentials: []
+            "}
+        )
+        .unwrap();
+        let cfg = KafkyConfig::load(tmp_cfg.path()).unwrap();
+        let problems: Vec<String> = cfg.validate().iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            problems,
+            vec![
+                "environments[0].brokers: at least one broker is required",
+                "environments[0].truststore.base64: invalid base64: Invalid byte 32, offset 3.",
+                "environments[0].credentials[0].ssl.certificate.path: cannot read /not/existing.pem: No such file or directory (os error 2)",
+                "environments[0].credentials[0].ssl.privateKey: privateKey is required unless the certificate is a keystore",
+                "environments[0].credentials[1].name: duplicated credential name ssl",
+                "environments[1].name: duplicated environment name dev",
+                "environments[1].credentials: at least one credential is required",
+            ]
+        );
+        match cfg.check_environment("dev", "ssl") {
+            Err(KafkyError::InvalidConfiguration(msg)) => {
+                assert!(msg
+                    .contains("; environments[0].credentials[0].ssl.certificate.path: cannot read"))
+            }
+            _ => panic!("invalid configuration expected"),
+        }
+    }
+
+    #[test]
+    fn check_environment_test() {
+        let mut tmp_cfg = tempfile::Builder::new().suffix(".yml").tempfile().unwrap();
+        write!(
+            tmp_cfg,
+            indoc! {"
+                environments:
+                  - name: dev
+                    brokers:
+                      - localhost:9092
+                    credentials:
+                      - name: plain
+                        plain:
+                          username: user
+                          password: password
+                      - name: ssl
+                        ssl:
+                          certificate:
+                            path: /not/existing.pem
+                          privateKey:
+                            path: /not/existing.key
+                  - name: prod
+                    brokers: []
+                    credentials:
+                      - name: plain
+                        plain:
+                          username: user
+                          password: password
+            "}
+        )
+        .unwrap();
+        let cfg = KafkyConfig::load(tmp_cfg.path()).unwrap();
+        assert!(!cfg.validate().is_empty());
+        assert!(cfg.check_environment("dev", "plain").is_ok());
+        match cfg.check_environment("dev", "ssl") {
+            Err(KafkyError::InvalidConfiguration(msg)) => {
+                assert!(msg.contains("environments[0].credentials[1].ssl.certificate.path"));
+                assert!(!msg.contains("environments[1]"));
+            }
+            _ => panic!("invalid configuration expected"),
+        }
+        assert!(cfg.check_environment("prod", "plain").is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce};
+use argon2::Argon2;
+use log::debug;
+use rand::RngCore;
+
+use crate::KafkyError;
+
+const VAULT_MAGIC: &[u8] = b"KAFKYVAULT";
+const VAULT_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = VAULT_MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+
+/// environment variable containing the vault passphrase, asked on the terminal when missing
+pub const VAULT_PASSPHRASE_ENV: &str = "KAFKY_VAULT_PASSPHRASE";
+
+/// Secrets store encrypted with AES-256-GCM using a key derived by Argon2 from a passphrase
+pub struct KafkyVault {
+    path: PathBuf,
+    passphrase: String,
+    secrets: BTreeMap<String, String>,
+}
+
+impl KafkyVault {
+    /// opens the vault at `path`, an empty vault is returned if the file doesn't exist yet
+    pub fn open(path: &Path, passphrase: &str) -> Result<Self, KafkyError> {
+        let secrets = if path.exists() {
+            debug!("opening vault {:?}", path);
+            Self::decrypt(&fs::read(path)?, passphrase)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(KafkyVault {
+            path: path.to_path_buf(),
+            passphrase: passphrase.to_string(),
+            secrets,
+        })
+    }
+
+    /// opens the existing vault asking the passphrase if not present in the KAFKY_VAULT_PASSPHRASE variable
+    pub fn unlock(path: &Path) -> Result<Self, KafkyError> {
+        if !path.exists() {
+            return Err(KafkyError::VaultError(format!(
+                "vault {} not found",
+                path.display()
+            )));
+        }
+        Self::open(path, &Self::passphrase(path, false)?)
+    }
+
+    /// opens the vault like `unlock`, the passphrase of a new one is asked twice
+    pub fn unlock_or_create(path: &Path) -> Result<Self, KafkyError> {
+        Self::open(path, &Self::passphrase(path, !path.exists())?)
+    }
+
+    fn passphrase(path: &Path, confirm: bool) -> Result<String, KafkyError> {
+        if let Ok(passphrase) = std::env::var(VAULT_PASSPHRASE_ENV) {
+            return Ok(passphrase);
+        }
+        let passphrase =
+            rpassword::prompt_password(format!("Vault passphrase ({}): ", path.display()))?;
+        if confirm && rpassword::prompt_password("Confirm vault passphrase: ")? != passphrase {
+            return Err(KafkyError::VaultError(
+                "passphrases don't match".to_string(),
+            ));
+        }
+        Ok(passphrase)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.secrets.get(name).map(|s| s.as_str())
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.secrets.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.secrets.remove(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.secrets.keys().map(|k| k.as_str()).collect()
+    }
+
+    pub fn save(&self) -> Result<(), KafkyError> {
+        debug!("saving vault {:?}", &self.path);
+        let content = Self::encrypt(&self.secrets, &self.passphrase)?;
+        fs::write(&self.path, content)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&self.path, fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(())
+    }
+
+    fn cipher(passphrase: &str, salt: &[u8]) -> Result<Aes256Gcm, KafkyError> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| KafkyError::VaultError(e.to_string()))?;
+        Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+    }
+
+    fn encrypt(
+        secrets: &BTreeMap<String, String>,
+        passphrase: &str,
+    ) -> Result<Vec<u8>, KafkyError> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let mut content = Vec::from(VAULT_MAGIC);
+        content.push(VAULT_VERSION);
+        content.extend_from_slice(&salt);
+        content.extend_from_slice(&nonce);
+
+        let plaintext = serde_json::to_vec(secrets)?;
+        let ciphertext = Self::cipher(passphrase, &salt)?
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: &content,
+                },
+            )
+            .map_err(|_| KafkyError::VaultError("encryption failure".to_string()))?;
+        content.extend(ciphertext);
+        Ok(content)
+    }
+
+    fn decrypt(content: &[u8], passphrase: &str) -> Result<BTreeMap<String, String>, KafkyError> {
+        if content.len() < HEADER_LEN || !content.starts_with(VAULT_MAGIC) {
+            return Err(KafkyError::VaultError("not a kafky vault".to_string()));
+        }
+        let (header, ciphertext) = content.split_at(HEADER_LEN);
+        if header[VAULT_MAGIC.len()] != VAULT_VERSION {
+            return Err(KafkyError::VaultError(format!(
+                "unsupported vault version {}",
+                header[VAULT_MAGIC.len()]
+            )));
+        }
+        let salt = &header[VAULT_MAGIC.len() + 1..VAULT_MAGIC.len() + 1 + SALT_LEN];
+        let nonce = &header[HEADER_LEN - NONCE_LEN..];
+        let plaintext = Self::cipher(passphrase, salt)?
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| {
+                KafkyError::VaultError("wrong passphrase or corrupted vault".to_string())
+            })?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vault_round_trip_test() -> Result<(), KafkyError> {
+        let vault_dir = tempfile::tempdir()?;
+        let vault_path = vault_dir.path().join("secrets.enc");
+
+        let mut vault = KafkyVault::open(&vault_path, "master")?;
+        vault.set("prod-password", "s3cr3t");
+        vault.set("dev-password", "dev");
+        vault.save()?;
+        assert!(!fs::read(&vault_path)?
+            .windows("s3cr3t".len())
+            .any(|w| w == b"s3cr3t"));
+
+        let mut vault = KafkyVault::open(&vault_path, "master")?;
+        assert_eq!(vault.get("prod-password"), Some("s3cr3t"));
+        assert_eq!(vault.names(), vec!["dev-password", "prod-password"]);
+        vault.remove("dev-password");
+        vault.save()?;
+        assert_eq!(
+            KafkyVault::open(&vault_path, "master")?.names(),
+            vec!["prod-password"]
+        );
+
+        assert!(matches!(
+            KafkyVault::open(&vault_path, "wrong"),
+            Err(KafkyError::VaultError(_))
+        ));
+
+        // reading a missing vault fails before asking a passphrase
+        match KafkyVault::unlock(&vault_dir.path().join("missing.enc")) {
+            Err(KafkyError::VaultError(message)) => assert!(message.ends_with("not found")),
+            _ => panic!("vault not found expected"),
+        }
+        Ok(())
+    }
+}
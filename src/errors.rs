@@ -11,26 +11,44 @@ pub enum KafkyError {
     ConfigurationNotFound(String),
     #[error("Invalid configuration {0}")]
     InvalidConfiguration(String),
+    #[error(
+        "The configuration {0} has the version {1}, this kafky supports up to the version {2}"
+    )]
+    UnsupportedConfigVersion(String, u64, u64),
     #[error("Environment parameter is mandatory")]
     EnvironmentParamNotFound(),
     #[error("Environment not found {0}, available environment {1}")]
     EnvironmentNotFound(String, String),
     #[error("No credential specified, available credentials {0}")]
     NoCredentialSpecified(String),
+    #[error("The environment {0} is read-only, {1} is not allowed")]
+    ReadOnlyEnvironment(String, String),
+    #[error("The environment {0} is protected and its name was not confirmed")]
+    ProtectedEnvironment(String),
     #[error("Parse error :{0}")]
     ParseError(String),
     #[error("Credential not found {0}, in the environment {1} available credentials {2}")]
     CredentialNotFound(String, String, String),
+    #[error("Environment {0} already exists")]
+    EnvironmentAlreadyExists(String),
+    #[error("Credential {0} already exists in the environment {1}")]
+    CredentialAlreadyExists(String, String),
     #[error("Kafka error:{0}")]
     KafkaError(String),
     #[error("Invalid command")]
     InvalidCommand(),
     #[error("Invalid offset")]
     InvalidOffset(),
+    #[error("Invalid offset {0}, expected OFFSET or PARTITION:OFFSET")]
+    InvalidPartitionOffset(String),
+    #[error("Invalid time {0}: {1}")]
+    InvalidTime(String, String),
     #[error("Error creating sample config: {0}")]
     CannotCreateSampleConfig(String),
     #[error("Topic not found: {0}")]
     TopicNotFound(String),
+    #[error("Partition {1} not found in the topic {0}")]
+    PartitionNotFound(String, i32),
     #[error("Invalid json: {0}")]
     InvalidJson(String),
     #[error("Exit")]
@@ -39,6 +57,34 @@ pub enum KafkyError {
     Readline(String),
     #[error("Key separator not found")]
     KeySeparatorNotFound(),
+    #[error("Invalid header {0}, expected KEY=VALUE")]
+    InvalidHeader(String),
+    #[error("Security protocol {0} of the environment {1} cannot be used with the credential {2}")]
+    InvalidSecurityProtocol(String, String, String),
+    #[error("OAuth token error: {0}")]
+    OAuthTokenError(String),
+    #[error("Cannot resolve the secret of the credential {1} in the environment {0}: {2}")]
+    SecretNotResolved(String, String, String),
+    #[error("Vault error: {0}")]
+    VaultError(String),
+    #[error("Cannot load the keystore of the credential {1} in the environment {0}: {2}")]
+    KeystoreError(String, String, String),
+    #[error("Invalid Kafka properties {0}: {1}")]
+    PropertiesError(String, String),
+    #[error("Certificate check failed: {0}")]
+    CertificateCheckFailed(String),
+    #[error("No schema registry configured in the environment {0}")]
+    SchemaRegistryNotConfigured(String),
+    #[error("Schema registry error: {0}")]
+    SchemaRegistryError(String),
+    #[error("Cannot decode the {0}: {1}")]
+    DecodeError(String, String),
+    #[error("Cannot encode the {0}: {1}")]
+    EncodeError(String, String),
+    #[error("Protobuf error: {0}")]
+    ProtobufError(String),
+    #[error("Invalid filter {0}: {1}")]
+    InvalidFilter(String, String),
 }
 
 impl From<KafkaError> for KafkyError {
@@ -5,20 +5,22 @@ use client::kafky_client::KafkyClient;
 use errors::KafkyError;
 use log::debug;
 use std::cell::RefCell;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 mod client;
 mod cmd;
 mod config;
 mod errors;
 
+/// environment variable containing the configuration file path
+const CONFIG_ENV: &str = "KAFKY_CONFIG";
+
 #[tokio::main]
 async fn main() -> Result<(), String> {
     env_logger::init();
 
-    let mut config_path = home::home_dir().expect("impossible to get the home folder");
-    debug!("home folder:{:?}", config_path);
-    config_path.push(".kafky/config.yml");
+    let config_path = config_path();
+    debug!("configuration file:{:?}", config_path);
 
     let cfg = load_config_or_create(&config_path).expect("configuration not found");
 
@@ -39,7 +41,29 @@ async fn main() -> Result<(), String> {
     }
 }
 
-fn load_config_or_create(config_path: &Path) -> Result<KafkyConfig, KafkyError> {
+/// `--config` argument, then `KAFKY_CONFIG` variable, then `~/.kafky/config.yml`.
+/// The arguments are scanned before clap because the configuration is needed to build the command
+fn config_path() -> PathBuf {
+    let args: Vec<String> = std::env::args().collect();
+    let config_arg = args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == "--config" {
+            args.get(i + 1).cloned()
+        } else {
+            arg.strip_prefix("--config=").map(|path| path.to_string())
+        }
+    });
+    match config_arg.or_else(|| std::env::var(CONFIG_ENV).ok()) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let mut config_path = home::home_dir().expect("impossible to get the home folder");
+            config_path.push(".kafky/config.yml");
+            config_path
+        }
+    }
+}
+
+fn load_config_or_create(config_path: &Path) -> Result<KafkyConfig<'_>, KafkyError> {
+    // RootCmd checks only the environment it connects to, the config commands fix the others
     match config::KafkyConfig::load(config_path) {
         Ok(config) => Ok(config),
         Err(e) => match e {